@@ -0,0 +1,241 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::word_tree::{Word, trim_last_letter};
+
+/// Most lines a snippet can have.
+const SNIPPET_LINES: usize = 12;
+/// How many files are tried before giving up on finding a snippet.
+const FILE_TRIES: usize = 20;
+/// Directories that hold build output or dependencies rather than code anyone wrote.
+const SKIPPED_DIRS: [&str; 3] = ["target", "node_modules", "build"];
+
+/// A language the code mode knows, by file extension. A snippet starts where one of the
+/// `item_starts` begins an unindented line, so it is a whole function or type where possible.
+struct Language {
+    extensions: &'static [&'static str],
+    item_starts: &'static [&'static str],
+}
+
+const LANGUAGES: [Language; 6] = [
+    Language {
+        extensions: &["rs"],
+        item_starts: &["fn ", "pub fn ", "pub(crate) fn ", "impl", "struct ", "pub struct ", "enum ", "pub enum ", "trait ", "pub trait "],
+    },
+    Language { extensions: &["py"], item_starts: &["def ", "async def ", "class "] },
+    Language {
+        extensions: &["js", "ts", "jsx", "tsx"],
+        item_starts: &["function ", "async function ", "class ", "export "],
+    },
+    Language { extensions: &["go"], item_starts: &["func ", "type "] },
+    Language { extensions: &["c", "h", "cpp", "hpp", "cc"], item_starts: &[] },
+    Language { extensions: &["java", "kt", "cs"], item_starts: &["public ", "private ", "class "] },
+];
+
+fn find_language(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?;
+    LANGUAGES.iter().find(|language| language.extensions.contains(&extension))
+}
+
+/// Source files to take code snippets from, given with `--code` as a file or a directory.
+pub struct CodeFiles {
+    files: Vec<PathBuf>,
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    if path.is_file() {
+        if find_language(path).is_some() {
+            files.push(path.to_path_buf());
+        }
+        return Ok(());
+    }
+
+    // The file type of an entry is the link itself for a symlink, so linked directories are not
+    // followed and a link back up the tree cannot loop.
+    let mut entries: Vec<(PathBuf, fs::FileType)> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.path(), entry.file_type().ok()?)))
+        .collect();
+    // Sorted so the same seed picks the same file.
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (entry, file_type) in entries {
+        let name = entry.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name) {
+            continue;
+        }
+        if file_type.is_dir() {
+            collect_files(&entry, files)?;
+        } else if file_type.is_symlink() && entry.is_dir() {
+            continue;
+        } else if find_language(&entry).is_some() {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Leading whitespace of a line, in indentation levels, and what is left after it.
+fn split_indent(line: &str, indent_width: usize) -> (usize, usize, &str) {
+    let rest = line.trim_start_matches([' ', '\t']);
+    let indent = &line[..line.len() - rest.len()];
+    let tabs = indent.matches('\t').count();
+    let spaces = indent.matches(' ').count();
+    (tabs + spaces / indent_width, spaces % indent_width, rest)
+}
+
+/// The smallest indentation made of spaces in the file, taken as one level.
+fn guess_indent_width(lines: &[&str]) -> usize {
+    lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .filter(|spaces| *spaces >= 2)
+        .min()
+        .unwrap_or(4)
+}
+
+/// Turns lines of code into words. Indentation becomes tab letters, typed with Tab, and every line
+/// ends with a newline letter, typed with Enter. Spaces stay with the word before them.
+fn code_words(lines: &[&str], indent_width: usize) -> VecDeque<Word> {
+    let mut words = VecDeque::new();
+    for line in lines {
+        let (levels, spaces, rest) = split_indent(line, indent_width);
+
+        let mut texts: Vec<String> = vec![];
+        if levels > 0 {
+            texts.push("\t".repeat(levels));
+        }
+        let mut text = " ".repeat(spaces);
+        for character in rest.chars() {
+            if character != ' ' && text.ends_with(' ') && !text.trim().is_empty() {
+                texts.push(std::mem::take(&mut text));
+            }
+            text.push(character);
+        }
+        text.push('\n');
+        texts.push(text);
+
+        let last = texts.len() - 1;
+        for (index, text) in texts.iter().enumerate() {
+            let mut word = Word::new(text);
+            word.ends_paragraph = index == last;
+            words.push_back(word);
+        }
+    }
+
+    trim_last_letter(&mut words);
+    words
+}
+
+/// Where a snippet of `lines` could start. Item starts where the language has them, otherwise
+/// any unindented line.
+fn snippet_starts(lines: &[&str], language: &Language) -> Vec<usize> {
+    let unindented = |line: &&str| !line.trim().is_empty() && !line.starts_with([' ', '\t']);
+    let item_starts: Vec<usize> = (0..lines.len())
+        .filter(|index| {
+            let line = lines[*index];
+            unindented(&line) && language.item_starts.iter().any(|start| line.starts_with(start))
+        })
+        .collect();
+    if !item_starts.is_empty() {
+        return item_starts;
+    }
+    (0..lines.len()).filter(|index| unindented(&lines[*index])).collect()
+}
+
+impl CodeFiles {
+    pub fn open(path: &str) -> Result<CodeFiles, io::Error> {
+        let mut files = vec![];
+        collect_files(Path::new(path), &mut files)?;
+        if files.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Found no source files in a language the code mode knows."));
+        }
+        Ok(CodeFiles { files })
+    }
+
+    /// Picks a file and a snippet from it, and tells which file it was. Blank lines and trailing
+    /// whitespace are left out.
+    pub fn snippet(&self, rng: &mut impl Rng) -> Result<(VecDeque<Word>, &Path), io::Error> {
+        for _ in 0..FILE_TRIES {
+            let path = self.files.choose(rng).unwrap();
+            // Files that are not UTF-8 are skipped, another file is tried instead.
+            let Ok(content) = fs::read_to_string(path) else {
+                continue;
+            };
+            let language = find_language(path).unwrap();
+
+            let lines: Vec<&str> = content.lines().map(|line| line.trim_end()).collect();
+            let starts = snippet_starts(&lines, language);
+            let Some(start) = starts.choose(rng).copied() else {
+                continue;
+            };
+            // Stop where the next item starts, so the snippet is one item when it is short enough.
+            let end = starts.iter().copied().find(|next| *next > start).unwrap_or(lines.len());
+
+            let snippet: Vec<&str> = lines[start..end]
+                .iter()
+                .filter(|line| !line.is_empty())
+                .take(SNIPPET_LINES)
+                .copied()
+                .collect();
+            return Ok((code_words(&snippet, guess_indent_width(&lines)), path));
+        }
+        Err(io::Error::new(io::ErrorKind::InvalidData, "Could not find a snippet in the source files."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    fn outputs(words: &VecDeque<Word>) -> Vec<&str> {
+        words.iter().map(|word| word.output.as_str()).collect()
+    }
+
+    #[test]
+    fn indentation_is_counted_in_levels() {
+        assert_eq!(split_indent("        x = 1", 4), (2, 0, "x = 1"));
+        assert_eq!(split_indent("\t\tx", 4), (2, 0, "x"));
+        assert_eq!(split_indent("      x", 4), (1, 2, "x"));
+        assert_eq!(split_indent("x", 4), (0, 0, "x"));
+    }
+
+    #[test]
+    fn code_lines_become_tabs_words_and_newlines() {
+        let words = code_words(&["fn main() {", "    let x = 1;", "}"], 4);
+
+        assert_eq!(outputs(&words), vec!["fn ", "main() ", "{\n", "\t", "let ", "x ", "= ", "1;\n", "}"]);
+        let paragraph_ends: Vec<bool> = words.iter().map(|word| word.ends_paragraph).collect();
+        assert_eq!(paragraph_ends, vec![false, false, true, false, false, false, false, true, true]);
+    }
+
+    #[test]
+    fn spaces_left_over_from_the_indentation_start_the_line() {
+        let words = code_words(&["      x"], 4);
+
+        assert_eq!(outputs(&words), vec!["\t", "  x"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn linked_directories_are_not_followed() {
+        let dir = ScratchDir::new("code-links");
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        std::os::unix::fs::symlink("..", dir.path().join("src").join("up")).unwrap();
+        std::os::unix::fs::symlink("src/main.rs", dir.path().join("linked.rs")).unwrap();
+
+        let mut files = vec![];
+        collect_files(dir.path(), &mut files).unwrap();
+
+        assert_eq!(files, vec![dir.path().join("linked.rs"), dir.path().join("src").join("main.rs")]);
+    }
+}
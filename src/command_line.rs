@@ -1,49 +1,186 @@
 use std::{env, process};
 
-use crate::{plot::{PlotData, PlotType}, statistics::{self, ALPHABET}};
+use crate::{decoration::Decorations, plot::{PlotData, PlotType}, store::StoreKind, word_tree::GenMode};
 
-#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Default)]
 pub enum GameMode {
     HELP,
     ENDLESS,
     STATS,
+    #[default]
     LESSON,
     PLOT,
+    /// A test that runs for this many seconds.
+    TIMED(u64),
+    /// A test of exactly this many words.
+    WORDS(u32),
 } 
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
+pub enum StatsView {
+    #[default]
+    Letters,
+    Ngrams,
+    Sessions,
+}
+
+#[derive(Debug, Default)]
 pub struct GameOpts {
     pub mode: GameMode,
     pub file: Option<String>,
     pub plot_data: Option<PlotData>,
+    pub generation: GenMode,
+    pub adaptive: bool,
+    pub ngram_drill: bool,
+    /// Wrong keys are typed into the text and have to be deleted with backspace.
+    pub real_typing: bool,
+    pub stats_view: StatsView,
+    /// Seed for the word generation, the same seed gives the same text.
+    pub seed: Option<u64>,
+    /// A text file to type in order instead of generated words.
+    pub text: Option<String>,
+    /// A source file or a directory of them to take code snippets from.
+    pub code: Option<String>,
+    pub decorations: Decorations,
+    /// Where the statistics are read from and saved to.
+    pub store: StoreKind,
+}
+
+impl GameOpts {
+    /// What kind of lesson these options give, as saved in the session history.
+    pub fn mode_name(&self) -> &'static str {
+        if self.text.is_some() {
+            return "text";
+        }
+        if self.code.is_some() {
+            return "code";
+        }
+        match self.mode {
+            GameMode::ENDLESS => "endless",
+            GameMode::TIMED(_) => "timed",
+            GameMode::WORDS(_) => "words",
+            _ => "lesson",
+        }
+    }
+
+    /// The flags that change what is typed or how, as saved in the session history.
+    pub fn option_names(&self) -> Vec<String> {
+        let flags = [
+            (matches!(self.generation, GenMode::Nonsense), "nonsense"),
+            (self.adaptive, "adaptive"),
+            (self.ngram_drill, "ngrams"),
+            (self.real_typing, "real"),
+            (self.decorations.punctuation, "punctuation"),
+            (self.decorations.numbers, "numbers"),
+            (self.decorations.caps, "caps"),
+        ];
+        flags.iter().filter(|flag| flag.0).map(|flag| flag.1.to_string()).collect()
+    }
 }
 
 fn check_plot_type(plot_type: String) -> bool {
     match plot_type.as_str() {
         "all" => true,
-        _ => ALPHABET.contains(&plot_type) && plot_type.len() == 1,
+        _ => plot_type.chars().count() == 1,
     }
 }
 
 pub fn show_help() {
-    println!("velogos [--help|--stats|--endless] [--plot wpm|accuracy|sin|square all|letters...] [--file ...]")
+    println!("velogos [--help|--stats [ngrams|sessions]|--endless] [--plot wpm|accuracy|sin|square all|letters...] [--file ...] [--nonsense] [--adaptive|--ngrams] [--time seconds|--words count] [--real] [--punctuation] [--numbers] [--caps] [--seed number|--text file|--code path] [--store json|sqlite]")
+}
+
+/// Removes `flag` from the arguments and tells if it was there. Used for flags that can be
+/// combined with any of the other options.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let position = args.iter().position(|arg| arg == flag);
+    if let Some(position) = position {
+        args.remove(position);
+        return true;
+    }
+    false
+}
+
+/// Removes `flag` and the value after it from the arguments. Exits with the help text if the
+/// flag is there without a value.
+fn take_value(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let position = args.iter().position(|arg| arg == flag)?;
+    if position + 1 >= args.len() {
+        show_help();
+        process::exit(1);
+    }
+    let value = args.remove(position + 1);
+    args.remove(position);
+    Some(value)
+}
+
+fn parse_seconds(value: String) -> u64 {
+    let seconds = value.parse::<u64>();
+    match seconds {
+        Ok(seconds) if seconds > 0 => seconds,
+        _ => {
+            println!("The time has to be a whole number of seconds, like 15, 30, 60 or 120.");
+            process::exit(1);
+        }
+    }
 }
 
-fn parse_long_plot(args: &Vec<String>) -> Option<GameOpts> {
+fn parse_seed(value: String) -> u64 {
+    let seed = value.parse::<u64>();
+    match seed {
+        Ok(seed) => seed,
+        _ => {
+            println!("The seed has to be a whole number, like the one shown after a lesson.");
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_store(value: String) -> StoreKind {
+    match value.as_str() {
+        "json" => StoreKind::Json,
+        "sqlite" => StoreKind::Sqlite,
+        _ => {
+            println!("The store has to be json or sqlite.");
+            process::exit(1);
+        }
+    }
+}
 
-    let plot_data: Option<PlotData>;
+fn parse_word_count(value: String) -> u32 {
+    let count = value.parse::<u32>();
+    match count {
+        Ok(count) if count > 0 => count,
+        _ => {
+            println!("The word count has to be a whole number above zero.");
+            process::exit(1);
+        }
+    }
+}
+
+/// Turns a plain lesson into a timed or word count test. Only one of them can be asked for,
+/// and not together with the other modes.
+fn set_test_mode(opts: &mut GameOpts, mode: GameMode) {
+    if !matches!(opts.mode, GameMode::LESSON) {
+        show_help();
+        process::exit(1);
+    }
+    opts.mode = mode;
+}
+
+fn parse_long_plot(args: &[String]) -> Option<GameOpts> {
 
     if !check_plot_type(args[3].clone()) {
         return None;
     }
 
-    if args[3].as_str() == "all" {
+    let plot_data = if args[3].as_str() == "all" {
         let plot_type = match args[2].as_str() {
             "wpm" => PlotType::AllWpm,
             "accuracy" => PlotType::AllAcc,
             _ => return None,
         };
-        plot_data = Some(PlotData { plot_type, letter: None })
+        Some(PlotData { plot_type, letter: None })
     }
     else {
         let letter = args[3].clone();
@@ -52,27 +189,27 @@ fn parse_long_plot(args: &Vec<String>) -> Option<GameOpts> {
             "accuracy" => PlotType::LetterAcc,
             _ => return None,
         };
-        plot_data = Some(PlotData { plot_type, letter: Some(letter) })
-    }
+        Some(PlotData { plot_type, letter: Some(letter) })
+    };
 
-    Some(GameOpts { mode: GameMode::PLOT, file: None, plot_data })
+    Some(GameOpts { mode: GameMode::PLOT, plot_data, ..Default::default() })
 }
 
 
-fn parse_three_or_more_options(args: &Vec<String>) -> Option<GameOpts> {
+fn parse_three_or_more_options(args: &[String]) -> Option<GameOpts> {
     if args.len() < 4 {
         return None;
     }
 
     let first_element = &args[1];
 
-    let game_opts = match first_element.as_str() {
+    match first_element.as_str() {
         "--plot" => {
             parse_long_plot(args)
         },
         _ => {
-            let first_part = args.clone();
-            let second_part = args.clone().split_off(1);
+            let first_part = args.to_vec();
+            let second_part = args.to_vec().split_off(1);
 
             let first_options = parse_one_option(&first_part);
             let second_options = parse_two_options(&second_part);
@@ -81,73 +218,83 @@ fn parse_three_or_more_options(args: &Vec<String>) -> Option<GameOpts> {
                 return None;
             }
 
-            Some(GameOpts { mode: first_options.unwrap().mode, file: second_options.unwrap().file, plot_data: None })
+            Some(GameOpts { mode: first_options.unwrap().mode, file: second_options.unwrap().file, ..Default::default() })
         }
-    };
-
-    game_opts
+    }
 }
 
-fn parse_two_options(args: &Vec<String>) -> Option<GameOpts> {
+fn parse_two_options(args: &[String]) -> Option<GameOpts> {
 
-    let first_opt = args.get(1);
-    if first_opt.is_none() {
-        return None;
-    }
-    let first = first_opt.unwrap();
+    let first = args.get(1)?;
 
     let mode: GameMode = match first.as_str() {
         "--plot" => GameMode::PLOT,
         "--file" => GameMode::LESSON,
+        "--stats" => GameMode::STATS,
         _ => { 
             show_help(); 
             process::exit(1) 
         },
     };
 
-    let second_opt = args.get(2);
-    if second_opt.is_none() {
-        return None;
-    }
-    let second = second_opt.unwrap();
+    let second = args.get(2)?;
 
     if let GameMode::PLOT = mode {
         if second.as_str() == "sin" {
-            return Some(GameOpts { mode, file: None, plot_data: Some(PlotData { plot_type: crate::plot::PlotType::Sin, letter: None })})
+            return Some(GameOpts { mode, plot_data: Some(PlotData { plot_type: crate::plot::PlotType::Sin, letter: None }), ..Default::default() })
         }
         if second.as_str() == "square" {
-            return Some(GameOpts { mode, file: None, plot_data: Some(PlotData { plot_type: crate::plot::PlotType::Square, letter: None })})
+            return Some(GameOpts { mode, plot_data: Some(PlotData { plot_type: crate::plot::PlotType::Square, letter: None }), ..Default::default() })
         }
         return None;
     }
 
-    Some(GameOpts { mode, file: Some(second.clone()), plot_data: None })
+    if let GameMode::STATS = mode {
+        if second.as_str() == "ngrams" {
+            return Some(GameOpts { mode, stats_view: StatsView::Ngrams, ..Default::default() })
+        }
+        if second.as_str() == "sessions" {
+            return Some(GameOpts { mode, stats_view: StatsView::Sessions, ..Default::default() })
+        }
+        return None;
+    }
+
+    Some(GameOpts { mode, file: Some(second.clone()), ..Default::default() })
 }
 
-fn parse_one_option(args: &Vec<String>) -> Option<GameOpts> {
+fn parse_one_option(args: &[String]) -> Option<GameOpts> {
 
-    let first_opt = args.get(1);
+    let first = args.get(1)?;
 
-    if first_opt.is_none() {
-        return None;
-    }
-    let first = first_opt.unwrap();
-
-    let opts = match first.as_str() {
-        "--help" => Some(GameOpts { mode: GameMode::HELP, file: None, plot_data: None }),
-        "--stats" => Some(GameOpts { mode: GameMode::STATS, file: None, plot_data: None }),
-        "--endless" => Some(GameOpts { mode: GameMode::ENDLESS, file: None, plot_data: None }),
+    match first.as_str() {
+        "--help" => Some(GameOpts { mode: GameMode::HELP, ..Default::default() }),
+        "--stats" => Some(GameOpts { mode: GameMode::STATS, ..Default::default() }),
+        "--endless" => Some(GameOpts { mode: GameMode::ENDLESS, ..Default::default() }),
         _ => None,
-    };
-
-    opts
+    }
 }
 
 pub fn parse_command_line() -> GameOpts {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    let nonsense = take_flag(&mut args, "--nonsense");
+    let adaptive = take_flag(&mut args, "--adaptive");
+    let ngram_drill = take_flag(&mut args, "--ngrams");
+    let real_typing = take_flag(&mut args, "--real");
+    let seconds = take_value(&mut args, "--time").map(parse_seconds);
+    let word_count = take_value(&mut args, "--words").map(parse_word_count);
+    let seed = take_value(&mut args, "--seed").map(parse_seed);
+    let text = take_value(&mut args, "--text");
+    let code = take_value(&mut args, "--code");
+    let store = take_value(&mut args, "--store").map(parse_store).unwrap_or_default();
+    let decorations = Decorations {
+        punctuation: take_flag(&mut args, "--punctuation"),
+        numbers: take_flag(&mut args, "--numbers"),
+        caps: take_flag(&mut args, "--caps"),
+    };
 
     let opts = match args.len() - 1 {
-        0 => Some(GameOpts { mode: GameMode::LESSON, file: None, plot_data: None }),
+        0 => Some(GameOpts { mode: GameMode::LESSON, ..Default::default() }),
         1 => parse_one_option(&args),
         2 => parse_two_options(&args),
         _ => parse_three_or_more_options(&args),
@@ -158,18 +305,47 @@ pub fn parse_command_line() -> GameOpts {
         process::exit(1);
     }
 
-    let opts = opts.unwrap();
+    let mut opts = opts.unwrap();
 
-    if let GameMode::HELP = opts.mode {
+    if nonsense {
+        opts.generation = GenMode::Nonsense;
+    }
+    if let Some(seconds) = seconds {
+        set_test_mode(&mut opts, GameMode::TIMED(seconds));
+    }
+    if let Some(word_count) = word_count {
+        set_test_mode(&mut opts, GameMode::WORDS(word_count));
+    }
+    opts.adaptive = adaptive;
+    opts.ngram_drill = ngram_drill;
+    opts.real_typing = real_typing;
+    opts.seed = seed;
+    opts.decorations = decorations;
+    opts.store = store;
+
+    // A lesson focuses on either weak letters or weak n-grams.
+    if adaptive && ngram_drill {
         show_help();
-        process::exit(0);
+        process::exit(1);
     }
-    if let GameMode::STATS = opts.mode {
-        let res = statistics::show_stats();
-        if res.is_err() {
-            println!("{:?}", res.err());
-            process::exit(1);
-        }
+
+    // A text is typed as it is, there is nothing to generate or focus on.
+    if text.is_some() && (nonsense || adaptive || ngram_drill || seed.is_some() || opts.file.is_some() || decorations.any()) {
+        show_help();
+        process::exit(1);
+    }
+    opts.text = text;
+
+    // A code lesson is one snippet, typed as it is.
+    let other_mode = !matches!(opts.mode, GameMode::LESSON);
+    if code.is_some() && (nonsense || adaptive || ngram_drill || opts.file.is_some() || opts.text.is_some() || decorations.any() || other_mode) {
+        show_help();
+        process::exit(1);
+    }
+    opts.code = code;
+
+    if let GameMode::HELP = opts.mode {
+        show_help();
         process::exit(0);
     }
 
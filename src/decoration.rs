@@ -0,0 +1,110 @@
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::word_tree::Word;
+
+/// Chance of a word getting a comma after it with `--punctuation`.
+const COMMA_CHANCE: f64 = 0.1;
+/// Chance of a sentence ending after a word with `--punctuation`.
+const SENTENCE_END_CHANCE: f64 = 0.1;
+/// Chance of a word being wrapped in quotes or parentheses with `--punctuation`.
+const WRAP_CHANCE: f64 = 0.05;
+/// Chance of a word being swapped for a group of digits with `--numbers`.
+const NUMBER_CHANCE: f64 = 0.1;
+/// Chance of a word being capitalised with `--caps` when there are no sentences to start.
+const CAPS_CHANCE: f64 = 0.2;
+
+const SENTENCE_ENDS: [(&str, u32); 3] = [(".", 8), ("?", 1), ("!", 1)];
+const OTHER_PUNCTUATION: [&str; 2] = [";", ":"];
+
+/// What is added to generated words to practise more than lowercase letters.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Decorations {
+    pub punctuation: bool,
+    pub numbers: bool,
+    pub caps: bool,
+}
+
+impl Decorations {
+    pub fn any(&self) -> bool {
+        self.punctuation || self.numbers || self.caps
+    }
+}
+
+/// Decorates generated words one at a time. Remembers if the last word ended a sentence, so the
+/// next one can start with a capital.
+pub struct Decorator {
+    decorations: Decorations,
+    sentence_start: bool,
+}
+
+fn capitalise(text: &str) -> String {
+    let mut characters = text.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
+
+fn digit_group(rng: &mut impl Rng) -> String {
+    let length = rng.random_range(1..=4);
+    let mut digits = rng.random_range(1..=9).to_string();
+    for _ in 1..length {
+        digits.push(char::from(b'0' + rng.random_range(0..10u8)));
+    }
+    digits
+}
+
+impl Decorator {
+    pub fn new(decorations: Decorations) -> Decorator {
+        Decorator { decorations, sentence_start: true }
+    }
+
+    /// Returns `word` with the decorations picked for it. The trailing space is kept.
+    pub fn decorate(&mut self, word: Word, rng: &mut impl Rng) -> Word {
+        if !self.decorations.any() {
+            return word;
+        }
+
+        let has_space = word.output.ends_with(' ');
+        let mut text = word.output.trim_end().to_string();
+
+        if self.decorations.numbers && rng.random_bool(NUMBER_CHANCE) {
+            text = digit_group(rng);
+        }
+
+        if self.decorations.caps {
+            let capitalise_here = if self.decorations.punctuation {
+                self.sentence_start
+            } else {
+                rng.random_bool(CAPS_CHANCE)
+            };
+            if capitalise_here {
+                text = capitalise(&text);
+            }
+        }
+
+        self.sentence_start = false;
+        if self.decorations.punctuation {
+            if rng.random_bool(WRAP_CHANCE) {
+                text = format!("\"{text}\"");
+            } else if rng.random_bool(WRAP_CHANCE) {
+                text = format!("({text})");
+            }
+
+            if rng.random_bool(SENTENCE_END_CHANCE) {
+                let (end, _) = SENTENCE_ENDS.choose_weighted(rng, |end| end.1).unwrap();
+                text.push_str(end);
+                self.sentence_start = true;
+            } else if rng.random_bool(COMMA_CHANCE) {
+                text.push(',');
+            } else if rng.random_bool(WRAP_CHANCE) {
+                text.push_str(OTHER_PUNCTUATION.choose(rng).unwrap());
+            }
+        }
+
+        if has_space {
+            text.push(' ');
+        }
+        Word::new(&text)
+    }
+}
@@ -0,0 +1,111 @@
+use std::{io, path::PathBuf};
+
+use json::{JsonValue, object};
+
+use crate::session_log::{SessionLog, attempt_in};
+use crate::statistics::{get_json_from_file, get_ngram_path, load_stats, lock_data, write_json_to_file};
+use crate::store::StatsStore;
+
+/// The statistics in JSON files in the data directory. Attempts from before there was a session
+/// history are in letter_data.json, the sessions in the session log and the n-gram totals in
+/// ngram_data.json.
+pub struct JsonStore {
+    log: SessionLog,
+    /// The attempts saved before there was a session history, as `{ key: { attempts: [] } }`.
+    /// Nothing is added to them any more, so they are read once.
+    legacy: JsonValue,
+}
+
+impl JsonStore {
+    /// Reads letter_data.json, upgrading it first if it is in an older layout. Saving only
+    /// appends to the session log, so it does not touch letter_data.json again.
+    pub fn open(log_path: PathBuf) -> Result<JsonStore, io::Error> {
+        let legacy = load_stats()?["legacy"].take();
+        Ok(JsonStore { log: SessionLog::new(log_path), legacy })
+    }
+
+    /// The session log, brought up to date with the file. A log with broken lines, like one cut
+    /// short by a crash, is compacted before it is used.
+    fn log(&mut self) -> Result<&SessionLog, io::Error> {
+        self.log.refresh()?;
+        if self.log.needs_compaction() {
+            self.log.compact()?;
+        }
+        Ok(&self.log)
+    }
+
+    pub fn legacy_attempts(&self) -> &JsonValue {
+        &self.legacy
+    }
+}
+
+impl StatsStore for JsonStore {
+    fn add_session(&mut self, session: &JsonValue) -> Result<(), io::Error> {
+        self.log.append(session)
+    }
+
+    fn latest_sessions(&mut self, count: usize) -> Result<Vec<JsonValue>, io::Error> {
+        self.log.latest(count, |_| true)
+    }
+
+    fn attempts(&mut self, key: &str) -> Result<JsonValue, io::Error> {
+        let mut attempts: Vec<JsonValue> = self.legacy[key]["attempts"].members().cloned().collect();
+        attempts.extend(self.log()?.attempts(key));
+        Ok(JsonValue::Array(attempts))
+    }
+
+    fn recent_attempts(&mut self, key: &str, count: usize) -> Result<JsonValue, io::Error> {
+        let sessions = self.log.latest(count, |session| !attempt_in(session, key).is_null())?;
+        let mut attempts: Vec<JsonValue> = sessions.iter().map(|session| attempt_in(session, key).clone()).collect();
+
+        if attempts.len() < count {
+            let legacy = &self.legacy[key]["attempts"];
+            let mut older: Vec<JsonValue> = legacy
+                .members()
+                .skip(legacy.len().saturating_sub(count - attempts.len()))
+                .cloned()
+                .collect();
+            older.extend(attempts);
+            attempts = older;
+        }
+        Ok(JsonValue::Array(attempts))
+    }
+
+    fn tracked_keys(&mut self) -> Result<Vec<String>, io::Error> {
+        let mut keys: Vec<String> = self.legacy
+            .entries()
+            .filter(|entry| !entry.1["attempts"].is_empty())
+            .map(|entry| entry.0.to_string())
+            .collect();
+        keys.extend(self.log()?.letters().cloned());
+        if !keys.iter().any(|key| key == "all") {
+            keys.push(String::from("all"));
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn add_ngrams(&mut self, lesson: &JsonValue) -> Result<(), io::Error> {
+        let path = get_ngram_path();
+        let _lock = lock_data(&path)?;
+        let mut content = get_json_from_file(&path)?;
+
+        for (ngram, result) in lesson.entries() {
+            let totals = &content[ngram];
+            let updated = object! {
+                count: totals["count"].as_u64().unwrap_or(0) + result["count"].as_u64().unwrap_or(0),
+                errors: totals["errors"].as_u64().unwrap_or(0) + result["errors"].as_u64().unwrap_or(0),
+                timed: totals["timed"].as_u64().unwrap_or(0) + result["timed"].as_u64().unwrap_or(0),
+                time_ms: totals["time_ms"].as_f64().unwrap_or(0.0) + result["time_ms"].as_f64().unwrap_or(0.0),
+            };
+            content[ngram] = updated;
+        }
+
+        write_json_to_file(&path, content)
+    }
+
+    fn ngram_totals(&mut self) -> Result<JsonValue, io::Error> {
+        get_json_from_file(&get_ngram_path())
+    }
+}
@@ -1,33 +1,52 @@
 use crossterm::{
     ExecutableCommand,
-    cursor::{MoveLeft, MoveRight, MoveToColumn, SetCursorStyle},
-    event::{self, Event, KeyCode},
-    style::{Color, ResetColor, SetForegroundColor},
-    terminal::{Clear, disable_raw_mode, enable_raw_mode},
+    cursor::SetCursorStyle,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::ResetColor,
+    terminal::{disable_raw_mode, enable_raw_mode},
 };
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::IndexedRandom};
 use std::{
-    cell::{Ref, RefCell},
-    io::{self, Write, stdout},
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    io::{self, stdout},
     rc::Rc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use crate::{
+    code::CodeFiles,
     command_line::{GameMode, GameOpts, parse_command_line, show_help},
+    decoration::{Decorations, Decorator},
+    passage::Passage,
     plot::{PlotType, get_letter_plot, get_sin, get_square},
-    statistics::{add_new_result, show_stats},
-    word_tree::{Node, Word},
+    statistics::{Report, ResultInfo, add_new_result, open_store, print_report, summarize, get_weakest_letters, get_weakest_ngrams, show_stats_view},
+    store::StatsStore,
+    view::{TextView, UPCOMING_LINES, letter_width},
+    word_tree::{GenMode, Letter, Word, WordTree, trim_last_letter},
 };
 
+mod code;
 mod command_line;
+mod decoration;
+mod passage;
 mod plot;
+mod json_store;
+mod schema;
+#[cfg(test)]
+mod scratch;
+mod session_log;
+mod sqlite_store;
 mod statistics;
+mod store;
+mod view;
 mod word_tree;
 
-const LINE_LENGTH: u32 = 20;
-const LINES_IN_LESSON: u16 = 1;
+const DEFAULT_LESSON_WORDS: u32 = 20;
+const FOCUS_LETTERS: usize = 3;
+const FOCUS_NGRAMS: usize = 3;
 
-fn take_char() -> Option<KeyCode> {
+fn take_event() -> Option<Event> {
     let res = event::poll(std::time::Duration::from_millis(100));
 
     if res.is_err() {
@@ -40,117 +59,639 @@ fn take_char() -> Option<KeyCode> {
             return Option::None;
         }
 
-        if let Event::Key(key_event) = event.unwrap() {
-            return Option::Some(key_event.code);
+        match event.unwrap() {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => return Option::Some(Event::Key(key_event)),
+            Event::Resize(columns, rows) => return Option::Some(Event::Resize(columns, rows)),
+            _ => {},
         }
     }
     Option::None
 }
 
-fn write_new_line(line: &String) -> Result<(), io::Error> {
-    let mut stdout = stdout();
-    stdout.execute(Clear(crossterm::terminal::ClearType::CurrentLine))?;
-    stdout.execute(MoveToColumn(0))?;
-    write!(stdout, "{}", line)?;
-    stdout.execute(MoveToColumn(0))?;
-    Ok(())
+fn classic_key(letter: &mut Letter, letter_index: &mut usize, code: KeyCode) {
+    let pressed_at = Instant::now();
+    letter.first_attempt.get_or_insert(pressed_at);
+    letter.keystrokes += 1;
+
+    if key_char(code) == Some(letter.letter) {
+        *letter_index += 1;
+        if letter.correct.is_none() {
+            letter.correct = Some(true);
+        }
+        letter.typed = Some(pressed_at);
+        letter.input = Some(letter.letter);
+    } else {
+        letter.correct = Some(false);
+        letter.wrong_attempts += 1;
+    }
 }
 
-fn wrong_char(correct_char: char) -> Result<(), io::Error> {
-    let mut stdout = stdout();
-    stdout.execute(SetForegroundColor(Color::Red))?;
-    write!(stdout, "{}", correct_char)?;
-    stdout.execute(ResetColor)?;
-    stdout.execute(MoveLeft(1))?;
-    stdout.flush()?;
-    Ok(())
+/// Ctrl+Backspace, Alt+Backspace and Ctrl+W delete back to the start of the word.
+fn real_typing_key(words: &mut [Word], word_index: &mut usize, letter_index: &mut usize, key: KeyEvent) {
+    let control = key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+
+    match key.code {
+        KeyCode::Backspace if control => delete_word(words, word_index, letter_index),
+        KeyCode::Char('h') | KeyCode::Char('w') if control => delete_word(words, word_index, letter_index),
+        KeyCode::Backspace => {
+            delete_letter(words, word_index, letter_index);
+        },
+        code => {
+            let Some(pressed) = key_char(code).filter(|_| !control) else {
+                return;
+            };
+            // The line is full of letters, some of them wrong. Those have to be deleted first.
+            if *word_index == words.len() {
+                return;
+            }
+
+            let letter = &mut words[*word_index].letters[*letter_index];
+            let pressed_at = Instant::now();
+            letter.first_attempt.get_or_insert(pressed_at);
+            letter.keystrokes += 1;
+            letter.input = Some(pressed);
+
+            if pressed == letter.letter {
+                if letter.correct.is_none() {
+                    letter.correct = Some(true);
+                }
+                letter.typed = Some(pressed_at);
+            } else {
+                letter.correct = Some(false);
+                letter.wrong_attempts += 1;
+            }
+            *letter_index += 1;
+        },
+    }
+}
+
+/// Returns the deleted letter, or None at the start of the line.
+fn delete_letter(words: &mut [Word], word_index: &mut usize, letter_index: &mut usize) -> Option<char> {
+    if *letter_index > 0 {
+        *letter_index -= 1;
+    } else if *word_index > 0 {
+        *word_index -= 1;
+        *letter_index = words[*word_index].letters.len() - 1;
+    } else {
+        return None;
+    }
+
+    let letter = &mut words[*word_index].letters[*letter_index];
+    letter.input = None;
+    Some(letter.letter)
+}
+
+/// Deletes any spaces before the cursor and then the word before them.
+fn delete_word(words: &mut [Word], word_index: &mut usize, letter_index: &mut usize) {
+    let mut deleted_letter = false;
+    loop {
+        let before = letter_before(words, *word_index, *letter_index);
+        if before.is_none() || (deleted_letter && before == Some(' ')) {
+            return;
+        }
+        let deleted = delete_letter(words, word_index, letter_index);
+        if deleted != Some(' ') {
+            deleted_letter = true;
+        }
+    }
 }
 
-fn gen_line(root: Ref<'_, Node>, length: u32) -> Vec<Word> {
+fn add_time(word: &mut Word, time: Duration) {
+    word.time = Some(word.time.unwrap_or_default() + time);
+}
+
+fn letter_before(words: &[Word], word_index: usize, letter_index: usize) -> Option<char> {
+    if letter_index > 0 {
+        return Some(words[word_index].letters[letter_index - 1].letter);
+    }
+    let previous_word = words[..word_index].last()?;
+    previous_word.letters.last().map(|letter| letter.letter)
+}
+
+fn line_is_correct(words: &[Word]) -> bool {
+    words
+        .iter()
+        .flat_map(|word| word.letters.iter())
+        .all(|letter| letter.input == Some(letter.letter))
+}
+
+#[derive(Clone)]
+enum Focus {
+    None,
+    Letters(Vec<char>),
+    Words(Vec<(String, u64)>),
+}
+
+/// About every other word is picked to practise the focus.
+fn gen_line(root: &WordTree, rng: &mut StdRng, length: u32, mode: GenMode, focus: &Focus) -> Result<Vec<Word>, io::Error> {
     let mut line = vec![];
     for _ in 0..length {
-        line.push(root.gen_word_with_space());
+        let use_focus = rng.random_bool(0.5);
+        let word = match focus {
+            Focus::Letters(letters) if use_focus && !letters.is_empty() => {
+                let letter = letters.choose(rng).unwrap();
+                root.gen_word_with_space_includes(rng, *letter)?
+            },
+            Focus::Words(words) if use_focus && !words.is_empty() => {
+                let (text, _) = words.choose_weighted(rng, |word| word.1).unwrap();
+                Word::new(&format!("{} ", text))
+            },
+            _ => root.gen_word_with_space(rng, mode),
+        };
+        line.push(word);
+    }
+    Ok(line)
+}
+
+fn print_focus(focus: &[String]) {
+    if focus.is_empty() {
+        println!("No statistics to focus on yet, practising everything.");
+    } else {
+        println!("Focusing on: {}", focus.join(", "));
+    }
+}
+
+fn get_focus_letters(store: &mut dyn StatsStore, root: &WordTree) -> Focus {
+    let weakest = match get_weakest_letters(store, root.alphabet()) {
+        Ok(weakest) => weakest,
+        Err(err) => {
+            println!("Could not read letter statistics: {}", err);
+            return Focus::None;
+        }
+    };
+
+    let focus: Vec<char> = weakest.into_iter().take(FOCUS_LETTERS).collect();
+
+    print_focus(&focus.iter().map(|letter| letter.to_string()).collect::<Vec<String>>());
+    Focus::Letters(focus)
+}
+
+fn get_focus_ngrams(store: &mut dyn StatsStore, root: &WordTree) -> Focus {
+    let weakest = match get_weakest_ngrams(store) {
+        Ok(weakest) => weakest,
+        Err(err) => {
+            println!("Could not read n-gram statistics: {}", err);
+            return Focus::None;
+        }
+    };
+
+    let mut focus = vec![];
+    let mut words = vec![];
+    for ngram in weakest {
+        if focus.len() >= FOCUS_NGRAMS {
+            break;
+        }
+        let words_with_ngram = root.words_containing(&ngram);
+        if words_with_ngram.is_empty() {
+            continue;
+        }
+        words.extend(words_with_ngram);
+        focus.push(ngram);
     }
-    line
+
+    print_focus(&focus);
+    Focus::Words(words)
+}
+
+/// Fixed-length lessons have all their words in `pending` from the start, the other modes
+/// generate more from the seeded `rng` when it runs dry.
+struct WordSource {
+    root: Option<Rc<WordTree>>,
+    rng: StdRng,
+    pending: VecDeque<Word>,
+    generate: bool,
+    generation: GenMode,
+    focus: Focus,
+    decorator: Decorator,
 }
 
-fn gen_string_line(line: &Vec<Word>) -> String {
-    let mut output_line = String::new();
-    for word in line {
-        output_line.push_str(&word.output);
+impl WordSource {
+    fn new(root: Option<Rc<WordTree>>, opts: &GameOpts, focus: Focus, seed: u64) -> Result<WordSource, io::Error> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let lesson_words = match opts.mode {
+            GameMode::LESSON => Some(DEFAULT_LESSON_WORDS),
+            GameMode::WORDS(count) => Some(count),
+            _ => None,
+        };
+
+        let mut decorator = Decorator::new(opts.decorations);
+        let mut pending = VecDeque::new();
+        if let Some(root) = &root
+            && let Some(count) = lesson_words
+        {
+            let line = gen_line(root, &mut rng, count, opts.generation, &focus)?;
+            pending.extend(line.into_iter().map(|word| decorator.decorate(word, &mut rng)));
+            trim_last_letter(&mut pending);
+        }
+
+        let generate = root.is_some() && lesson_words.is_none();
+        Ok(WordSource { root, rng, pending, generate, generation: opts.generation, focus, decorator })
+    }
+
+    fn from_words(page: VecDeque<Word>) -> WordSource {
+        WordSource {
+            root: None,
+            rng: StdRng::seed_from_u64(0),
+            pending: page,
+            generate: false,
+            generation: GenMode::Dictionary,
+            focus: Focus::None,
+            decorator: Decorator::new(Decorations::default()),
+        }
     }
-    output_line
+
+    /// Returns an empty line when a lesson of fixed length has run out.
+    fn next_line(&mut self, width: usize, min_words: usize) -> Result<Vec<Word>, io::Error> {
+        let mut line: Vec<Word> = vec![];
+        let mut line_width = 0;
+        loop {
+            if self.pending.is_empty() {
+                let Some(root) = self.root.as_ref().filter(|_| self.generate) else {
+                    break;
+                };
+                let line = gen_line(root, &mut self.rng, 1, self.generation, &self.focus)?;
+                for word in line {
+                    let word = self.decorator.decorate(word, &mut self.rng);
+                    self.pending.push_back(word);
+                }
+            }
+
+            let word_width: usize = self.pending[0].letters.iter().map(|letter| letter_width(letter.letter)).sum();
+            if line.len() >= min_words && line_width + word_width > width {
+                break;
+            }
+            line_width += word_width;
+            let word = self.pending.pop_front().unwrap();
+            let ends_paragraph = word.ends_paragraph;
+            line.push(word);
+            if ends_paragraph && line.len() >= min_words {
+                break;
+            }
+        }
+        Ok(line)
+    }
+
+    fn fill_upcoming(&mut self, upcoming: &mut VecDeque<Vec<Word>>) -> Result<(), io::Error> {
+        while upcoming.len() < UPCOMING_LINES {
+            let line = self.next_line(get_line_width(), 1)?;
+            if line.is_empty() {
+                break;
+            }
+            upcoming.push_back(line);
+        }
+        Ok(())
+    }
+
+    fn give_back(&mut self, words: Vec<Word>) {
+        for word in words.into_iter().rev() {
+            self.pending.push_front(word);
+        }
+    }
+}
+
+/// The current line starts again at the word being typed, so it still fits the new width.
+fn relayout(
+    source: &mut WordSource,
+    words: &mut Vec<Word>,
+    upcoming: &mut VecDeque<Vec<Word>>,
+    word_index: &mut usize,
+    old_lines: &mut Vec<Word>,
+) -> Result<(), io::Error> {
+    let done = words[..*word_index]
+        .iter()
+        .take_while(|word| line_is_correct(std::slice::from_ref(word)))
+        .count();
+    old_lines.extend(words.drain(..done));
+    *word_index -= done;
+
+    let mut rest: Vec<Word> = std::mem::take(words);
+    for line in upcoming.drain(..) {
+        rest.extend(line);
+    }
+    let min_words = (*word_index + 1).min(rest.len());
+    source.give_back(rest);
+
+    *words = source.next_line(get_line_width(), min_words)?;
+    source.fill_upcoming(upcoming)?;
+    Ok(())
+}
+
+fn status_line(old_lines: &[Word], done_in_line: &[Word], elapsed: Duration, time_limit: Option<Duration>, real_typing: bool) -> String {
+    let done: Vec<&Word> = old_lines.iter().chain(done_in_line.iter()).collect();
+    let summary = summarize(&done, real_typing);
+
+    let time = match time_limit {
+        Some(time_limit) => format!("left {}s", (time_limit - elapsed).as_secs_f64().ceil() as u64),
+        None => format!("time {}:{:02}", elapsed.as_secs() / 60, elapsed.as_secs() % 60),
+    };
+    format!(
+        "{time}  wpm {:.0}  raw {:.0}  acc {:.1}%  words {}  (ESC to stop)",
+        summary.wpm,
+        summary.raw_wpm,
+        summary.accuracy,
+        done.len(),
+    )
 }
 
-fn typing_loop(root: Rc<RefCell<Node>>, opts: GameOpts) -> Result<(), io::Error> {
-    println!("Click ESC to stop.");
+fn cursor_column(words: &[Word], word_index: usize, letter_index: usize) -> usize {
+    let letters_before = words[..word_index]
+        .iter()
+        .flat_map(|word| word.letters.iter())
+        .chain(words.get(word_index).into_iter().flat_map(|word| word.letters[..letter_index].iter()));
+    letters_before.map(|letter| letter_width(letter.letter)).sum()
+}
+
+/// Enter and Tab are typed keys in code.
+fn key_char(code: KeyCode) -> Option<char> {
+    match code {
+        KeyCode::Char(character) => Some(character),
+        KeyCode::Enter => Some('\n'),
+        KeyCode::Tab => Some('\t'),
+        _ => None,
+    }
+}
+
+fn get_line_width() -> usize {
+    let size = crossterm::terminal::size();
+    match size {
+        // Leave the last column free so the cursor never wraps onto the next row.
+        Ok((columns, _)) => (columns as usize).saturating_sub(1).max(1),
+        Err(_) => 79,
+    }
+}
+
+/// Returns None if nothing was saved.
+fn typing_loop(store: &mut dyn StatsStore, mut source: WordSource, opts: &GameOpts, info: &ResultInfo) -> Result<Option<Report>, io::Error> {
+    let mut view = TextView::new(!opts.real_typing)?;
     let mut old_lines = vec![];
 
-    let mut words = gen_line(root.borrow(), LINE_LENGTH);
-    let mut line = gen_string_line(&words);
+    let mut words = source.next_line(get_line_width(), 1)?;
+    let mut upcoming = VecDeque::new();
+    source.fill_upcoming(&mut upcoming)?;
 
     let mut word_index = 0;
     let mut letter_index = 0;
     let mut word_now = Instant::now();
 
-    let mut completed_lines = 0u16;
-    write_new_line(&line)?;
+    view.draw(&words, &upcoming, 0)?;
+
+    let time_limit = match opts.mode {
+        GameMode::TIMED(seconds) => Some(Duration::from_secs(seconds)),
+        _ => None,
+    };
+    let mut test_start: Option<Instant> = None;
+    let mut shown_status = String::new();
+    let mut report = None;
 
     loop {
-        let current_word = &mut words[word_index];
-        let code_opt = take_char();
-        if code_opt.is_some() {
-            let pressed_char = code_opt.unwrap();
-            if pressed_char == KeyCode::Esc {
-                add_new_result(old_lines);
+        let elapsed = test_start.map(|start| start.elapsed()).unwrap_or(Duration::ZERO);
+        if let Some(time_limit) = time_limit
+            && elapsed >= time_limit
+        {
+            old_lines.extend(words.drain(..word_index));
+            report = Some(add_new_result(store, old_lines, ResultInfo { duration: Some(time_limit), ..info.clone() }));
+            break;
+        }
+
+        let status = status_line(&old_lines, &words[..word_index], elapsed, time_limit, opts.real_typing);
+        if status != shown_status {
+            view.draw_status(&status)?;
+            shown_status = status;
+        }
+
+        let event_opt = take_event();
+        if let Some(Event::Resize(_, _)) = event_opt {
+            relayout(&mut source, &mut words, &mut upcoming, &mut word_index, &mut old_lines)?;
+            view.clear()?;
+            view.draw(&words, &upcoming, cursor_column(&words, word_index, letter_index))?;
+        }
+
+        if let Some(Event::Key(key)) = event_opt {
+            if key.code == KeyCode::Esc {
+                // A timed test stopped early is not comparable to full runs, so it is not saved.
+                if time_limit.is_none() {
+                    report = Some(add_new_result(store, old_lines, info.clone()));
+                }
                 break;
             }
+            // The first word is timed from the first key, not from when the text was shown.
+            if test_start.is_none() {
+                test_start = Some(Instant::now());
+                word_now = Instant::now();
+            }
 
-            let correct_letter = &mut current_word.letters[letter_index];
-            let correct_char = correct_letter.letter;
-
-            if pressed_char == KeyCode::Char(correct_char) {
-                stdout().execute(MoveRight(1))?;
-                letter_index += 1;
-                if correct_letter.correct.is_none() {
-                    correct_letter.correct = Some(true);
+            if opts.real_typing {
+                let word_before = word_index;
+                real_typing_key(&mut words, &mut word_index, &mut letter_index, key);
+                // Backspacing into an earlier word times it again from here. The time spent since
+                // the last word was finished goes to the word that was left.
+                if word_index < word_before {
+                    let left = word_before.min(words.len() - 1);
+                    add_time(&mut words[left], word_now.elapsed());
+                    word_now = Instant::now();
                 }
             } else {
-                wrong_char(correct_char)?;
-                correct_letter.correct = Some(false);
+                classic_key(&mut words[word_index].letters[letter_index], &mut letter_index, key.code);
             }
+            view.draw(&words, &upcoming, cursor_column(&words, word_index, letter_index))?;
         }
 
-        if letter_index == current_word.letters.len() {
+        if word_index < words.len() && letter_index == words[word_index].letters.len() {
             letter_index = 0;
+            add_time(&mut words[word_index], word_now.elapsed());
             word_index += 1;
-            current_word.time = Some(word_now.elapsed());
             word_now = Instant::now();
         }
 
-        if word_index == words.len() {
-            completed_lines += 1;
+        if word_index == words.len() && line_is_correct(&words) {
             word_index = 0;
 
             for word in words {
                 old_lines.push(word);
             }
 
-            if let GameMode::LESSON = opts.mode {
-                if completed_lines >= LINES_IN_LESSON {
-                    add_new_result(old_lines);
-                    break;
-                }
+            let next = upcoming.pop_front();
+            if next.is_none() {
+                report = Some(add_new_result(store, old_lines, info.clone()));
+                break;
             }
 
-            words = gen_line(root.borrow(), LINE_LENGTH);
-            line = gen_string_line(&words);
-            write_new_line(&line)?;
+            words = next.unwrap();
+            source.fill_upcoming(&mut upcoming)?;
+            view.draw(&words, &upcoming, 0)?;
+        }
+    }
+    view.leave()?;
+    Ok(report)
+}
+
+enum AfterLesson {
+    Retry,
+    NewLesson,
+    Quit,
+}
+
+fn ask_after_lesson() -> Result<AfterLesson, io::Error> {
+    println!();
+    println!("r: retry this text, n: new lesson, q: quit");
+
+    enable_raw_mode()?;
+    let choice = loop {
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            match key.code {
+                KeyCode::Char('r') => break AfterLesson::Retry,
+                KeyCode::Char('n') => break AfterLesson::NewLesson,
+                KeyCode::Char('q') | KeyCode::Esc => break AfterLesson::Quit,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break AfterLesson::Quit,
+                _ => {},
+            }
+        }
+    };
+    disable_raw_mode()?;
+    Ok(choice)
+}
+
+fn run_lesson(store: &mut dyn StatsStore, source: WordSource, opts: &GameOpts, info: &ResultInfo) -> Result<Option<Report>, io::Error> {
+    let mut stdout = stdout();
+
+    enable_raw_mode()?;
+    stdout.execute(SetCursorStyle::SteadyBar)?;
+
+    let res = typing_loop(store, source, opts, info);
+
+    stdout.execute(SetCursorStyle::DefaultUserShape)?;
+    stdout.execute(ResetColor)?;
+    disable_raw_mode()?;
+    println!();
+
+    match res {
+        Ok(report) => Ok(report),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            Ok(None)
+        }
+    }
+}
+
+fn result_info(opts: &GameOpts) -> ResultInfo {
+    ResultInfo {
+        mode: String::from(opts.mode_name()),
+        options: opts.option_names(),
+        source: None,
+        seed: None,
+        duration: None,
+        real_typing: opts.real_typing,
+    }
+}
+
+/// `next_source` is told whether to move on to a new text or give the last one again.
+fn practise(
+    store: &mut dyn StatsStore,
+    opts: &GameOpts,
+    mut next_source: impl FnMut(&mut dyn StatsStore, bool) -> Result<Option<(WordSource, ResultInfo)>, io::Error>,
+    mut finished: impl FnMut(&Report) -> Result<(), io::Error>,
+) -> Result<(), io::Error> {
+    let mut new_text = false;
+    while let Some((source, info)) = next_source(store, new_text)? {
+        let Some(report) = run_lesson(store, source, opts, &info)? else {
+            break;
+        };
+
+        println!();
+        print_report(&report);
+        if let Some(seed) = info.seed {
+            println!("seed:        {seed}");
+        }
+        finished(&report)?;
+        match ask_after_lesson()? {
+            AfterLesson::Retry => new_text = false,
+            AfterLesson::NewLesson => new_text = true,
+            AfterLesson::Quit => break,
         }
     }
     Ok(())
 }
 
+fn practise_words(store: &mut dyn StatsStore, root: Rc<WordTree>, file_name: &str, opts: &GameOpts) -> Result<(), io::Error> {
+    let mut focus = get_focus(store, &root, opts);
+    let mut seed = opts.seed.unwrap_or_else(|| rand::rng().random());
+
+    let next_source = |store: &mut dyn StatsStore, new_text: bool| {
+        if new_text {
+            focus = get_focus(store, &root, opts);
+            seed = rand::rng().random();
+        }
+        let source = match WordSource::new(Some(root.clone()), opts, focus.clone(), seed) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return Ok(None);
+            }
+        };
+        let info = ResultInfo { source: Some(file_name.to_string()), seed: Some(seed), ..result_info(opts) };
+        Ok(Some((source, info)))
+    };
+    practise(store, opts, next_source, |_| Ok(()))
+}
+
+fn practise_text(store: &mut dyn StatsStore, passage: Passage, opts: &GameOpts) -> Result<(), io::Error> {
+    let page_words = match opts.mode {
+        GameMode::LESSON => None,
+        GameMode::WORDS(count) => Some(count as usize),
+        _ => Some(usize::MAX),
+    };
+    // Both closures use the passage, one to read the page and the other to save the progress.
+    let passage = RefCell::new(passage);
+    let start = Cell::new(passage.borrow().position());
+
+    let next_source = |_: &mut dyn StatsStore, new_text: bool| {
+        if new_text {
+            start.set(passage.borrow().position());
+        }
+        let source = WordSource::from_words(passage.borrow().page(start.get(), page_words));
+        Ok(Some((source, ResultInfo { source: opts.text.clone(), ..result_info(opts) })))
+    };
+    let finished = |report: &Report| {
+        if passage.borrow_mut().advance(start.get(), report.word_count)? {
+            println!("You have reached the end of the text, the next lesson starts from the beginning.");
+        }
+        Ok(())
+    };
+    practise(store, opts, next_source, finished)
+}
+
+fn practise_code(store: &mut dyn StatsStore, files: CodeFiles, opts: &GameOpts) -> Result<(), io::Error> {
+    let mut seed = opts.seed.unwrap_or_else(|| rand::rng().random());
+
+    let next_source = |_: &mut dyn StatsStore, new_text: bool| {
+        if new_text {
+            seed = rand::rng().random();
+        }
+        let (snippet, file) = match files.snippet(&mut StdRng::seed_from_u64(seed)) {
+            Ok(snippet) => snippet,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return Ok(None);
+            }
+        };
+        let info = ResultInfo { source: Some(file.display().to_string()), seed: Some(seed), ..result_info(opts) };
+        Ok(Some((WordSource::from_words(snippet), info)))
+    };
+    practise(store, opts, next_source, |_| Ok(()))
+}
+
+fn get_focus(store: &mut dyn StatsStore, root: &WordTree, opts: &GameOpts) -> Focus {
+    if opts.ngram_drill {
+        get_focus_ngrams(store, root)
+    } else if opts.adaptive {
+        get_focus_letters(store, root)
+    } else {
+        Focus::None
+    }
+}
+
 fn main() -> Result<(), io::Error> {
     let opts: GameOpts = parse_command_line();
 
@@ -158,8 +699,20 @@ fn main() -> Result<(), io::Error> {
         show_help();
         return Ok(());
     }
+
+    let mut store = match open_store(opts.store) {
+        Ok(store) => store,
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
     if let GameMode::STATS = opts.mode {
-        show_stats()?;
+        if let Err(err) = show_stats_view(store.as_mut(), &opts.stats_view) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
         return Ok(());
     }
 
@@ -174,46 +727,110 @@ fn main() -> Result<(), io::Error> {
         let plot = match plot_data.plot_type {
             PlotType::Sin => get_sin(),
             PlotType::Square => get_square(),
-            _ => get_letter_plot(plot_data),
+            _ => get_letter_plot(store.as_mut(), plot_data),
         };
 
         println!("{}", plot);
         return Ok(());
     }
 
+    if let Some(text_file) = &opts.text {
+        let passage = Passage::open(text_file);
+        if let Err(err) = passage {
+            println!("{}\nFile name: {text_file}", err);
+            return Ok(());
+        }
+        return practise_text(store.as_mut(), passage.unwrap(), &opts);
+    }
+
+    if let Some(code_path) = &opts.code {
+        let files = CodeFiles::open(code_path);
+        if let Err(err) = files {
+            println!("{}\nPath: {code_path}", err);
+            return Ok(());
+        }
+        return practise_code(store.as_mut(), files.unwrap(), &opts);
+    }
+
     let mut file_name = String::from("1000-words");
 
     if opts.file.is_some() {
         file_name = opts.file.clone().unwrap();
     }
 
-    let root = word_tree::Node::new(file_name.clone());
+    let root = WordTree::new(file_name.clone());
+
+    if let Err(err) = root {
+        println!("{}\nFile name: {file_name}", err);
+        return Ok(());
+    }
+
+    practise_words(store.as_mut(), Rc::new(root.unwrap()), &file_name, &opts)
+}
 
-    if root.is_err() {
-        let err_opt = root.err();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if err_opt.is_some() {
-            let err = err_opt.unwrap();
-            println!("{}\nFile name: {file_name}", err.to_string());
+    fn press(words: &mut [Word], word_index: &mut usize, letter_index: &mut usize, code: KeyCode, modifiers: KeyModifiers) {
+        real_typing_key(words, word_index, letter_index, KeyEvent::new(code, modifiers));
+        // What typing_loop does when the cursor reaches the end of a word.
+        if *word_index < words.len() && *letter_index == words[*word_index].letters.len() {
+            *letter_index = 0;
+            *word_index += 1;
         }
-        return Ok(());
     }
 
-    let mut stdout = stdout();
+    fn type_text(words: &mut [Word], word_index: &mut usize, letter_index: &mut usize, text: &str) {
+        for pressed in text.chars() {
+            press(words, word_index, letter_index, KeyCode::Char(pressed), KeyModifiers::NONE);
+        }
+    }
 
-    enable_raw_mode()?;
-    stdout.execute(SetCursorStyle::SteadyBar)?;
+    fn inputs(words: &[Word]) -> String {
+        words.iter().flat_map(|word| word.letters.iter()).filter_map(|letter| letter.input).collect()
+    }
 
-    let res = typing_loop(root.unwrap(), opts);
+    #[test]
+    fn backspace_goes_back_into_the_previous_word() {
+        let mut words = vec![Word::new("ab "), Word::new("cd ")];
+        let (mut word_index, mut letter_index) = (0, 0);
 
-    if res.is_err() {
-        let err = res.unwrap_err();
-        eprintln!("Error: {}", err);
+        type_text(&mut words, &mut word_index, &mut letter_index, "ab c");
+        assert_eq!((word_index, letter_index), (1, 1));
+
+        press(&mut words, &mut word_index, &mut letter_index, KeyCode::Backspace, KeyModifiers::NONE);
+        press(&mut words, &mut word_index, &mut letter_index, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!((word_index, letter_index), (0, 2));
+        assert_eq!(inputs(&words), "ab");
+
+        type_text(&mut words, &mut word_index, &mut letter_index, " cd ");
+        assert_eq!((word_index, letter_index), (2, 0));
+        assert!(line_is_correct(&words));
     }
 
-    stdout.execute(SetCursorStyle::DefaultUserShape)?;
-    stdout.execute(ResetColor)?;
-    disable_raw_mode()?;
-    println!();
-    Ok(())
+    #[test]
+    fn delete_word_stops_at_the_start_of_the_word() {
+        let mut words = vec![Word::new("ab "), Word::new("cd ")];
+        let (mut word_index, mut letter_index) = (0, 0);
+
+        type_text(&mut words, &mut word_index, &mut letter_index, "ab cx");
+        press(&mut words, &mut word_index, &mut letter_index, KeyCode::Backspace, KeyModifiers::CONTROL);
+        assert_eq!((word_index, letter_index), (1, 0));
+        assert_eq!(inputs(&words), "ab ");
+
+        // From the start of a word, the space before it and the word before that go.
+        press(&mut words, &mut word_index, &mut letter_index, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert_eq!((word_index, letter_index), (0, 0));
+        assert_eq!(inputs(&words), "");
+        assert_eq!(delete_letter(&mut words, &mut word_index, &mut letter_index), None);
+    }
+
+    #[test]
+    fn time_is_added_for_a_word_typed_again() {
+        let mut word = Word::new("ab ");
+        add_time(&mut word, Duration::from_millis(300));
+        add_time(&mut word, Duration::from_millis(200));
+        assert_eq!(word.time, Some(Duration::from_millis(500)));
+    }
 }
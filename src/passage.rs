@@ -0,0 +1,168 @@
+use std::{collections::VecDeque, fs, io};
+
+use crate::{
+    statistics::{get_text_position, save_text_position},
+    word_tree::{Letter, Word, trim_last_letter},
+};
+
+/// How many words a lesson from a text has at least. The page goes on to the end of the sentence.
+const PAGE_WORDS: usize = 50;
+/// How far past `PAGE_WORDS` a page can go looking for the end of a sentence.
+const PAGE_SLACK: usize = 30;
+
+/// A text from `--text`, typed in order with its punctuation, capitals and paragraphs. How far
+/// the user has come is saved, so long texts like books can be typed over many sessions.
+pub struct Passage {
+    /// The file the text came from, used to save the progress.
+    key: String,
+    /// Every word of the text and whether it ends a paragraph.
+    words: Vec<(String, bool)>,
+    position: usize,
+}
+
+/// Swaps typographic characters that are not on a normal keyboard for the ones that are.
+fn normalize(text: &str) -> String {
+    text.chars()
+        .flat_map(|character| match character {
+            '\u{2018}' | '\u{2019}' | '\u{201B}' => vec!['\''],
+            '\u{201C}' | '\u{201D}' | '\u{201F}' => vec!['"'],
+            '\u{2013}' | '\u{2014}' => vec!['-'],
+            '\u{2026}' => vec!['.', '.', '.'],
+            '\u{00A0}' => vec![' '],
+            _ => vec![character],
+        })
+        .collect()
+}
+
+/// Splits a text into words, paragraphs being separated by blank lines.
+fn split_words(text: &str) -> Vec<(String, bool)> {
+    let mut words = vec![];
+    let mut paragraph: Vec<&str> = vec![];
+    for line in text.lines().chain(std::iter::once("")) {
+        if !line.trim().is_empty() {
+            paragraph.extend(line.split_whitespace());
+            continue;
+        }
+        let paragraph_length = paragraph.len();
+        for (index, word) in paragraph.drain(..).enumerate() {
+            words.push((word.to_string(), index + 1 == paragraph_length));
+        }
+    }
+    words
+}
+
+fn ends_sentence(word: &str) -> bool {
+    let word = word.trim_end_matches(['"', '\'', ')']);
+    word.ends_with(['.', '!', '?'])
+}
+
+impl Passage {
+    pub fn open(file_name: &str) -> Result<Passage, io::Error> {
+        let text = fs::read_to_string(file_name)?;
+        let words = split_words(&normalize(&text));
+        if words.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "The text file has no words in it."));
+        }
+
+        let key = fs::canonicalize(file_name)?.to_string_lossy().to_string();
+        let position = get_text_position(&key)?;
+        let position = if position < words.len() { position } else { 0 };
+
+        Ok(Passage { key, words, position })
+    }
+
+    /// Where the next lesson starts.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The words of a lesson starting at `start`. With no `word_count` the page is about
+    /// `PAGE_WORDS` long and ends at a sentence or paragraph, and with `usize::MAX` it is the rest
+    /// of the text.
+    pub fn page(&self, start: usize, word_count: Option<usize>) -> VecDeque<Word> {
+        let end = match word_count {
+            Some(word_count) => start.saturating_add(word_count).min(self.words.len()),
+            None => self.page_end(start),
+        };
+
+        let mut page: VecDeque<Word> = self.words[start..end]
+            .iter()
+            .map(|(text, ends_paragraph)| {
+                let mut word = Word::new(text);
+                word.letters.push(Letter::new(' '));
+                word.output.push(' ');
+                word.ends_paragraph = *ends_paragraph;
+                word
+            })
+            .collect();
+
+        trim_last_letter(&mut page);
+        page
+    }
+
+    fn page_end(&self, start: usize) -> usize {
+        let shortest = (start + PAGE_WORDS).min(self.words.len());
+        let longest = (shortest + PAGE_SLACK).min(self.words.len());
+        for end in shortest..longest {
+            let (text, ends_paragraph) = &self.words[end - 1];
+            if *ends_paragraph || ends_sentence(text) {
+                return end;
+            }
+        }
+        longest
+    }
+
+    /// Moves on to `typed_words` past `start` and saves it. Starts over when the end of the text
+    /// has been reached, and tells if it did.
+    pub fn advance(&mut self, start: usize, typed_words: usize) -> Result<bool, io::Error> {
+        self.position = start + typed_words;
+        let finished = self.position >= self.words.len();
+        if finished {
+            self.position = 0;
+        }
+        save_text_position(&self.key, self.position)?;
+        Ok(finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passage(text: &str) -> Passage {
+        Passage { key: String::new(), words: split_words(text), position: 0 }
+    }
+
+    #[test]
+    fn paragraphs_end_at_blank_lines() {
+        let words = split_words("One two\nthree.\n\n  \nFour  five\n");
+
+        let expected = [("One", false), ("two", false), ("three.", true), ("Four", false), ("five", true)];
+        assert_eq!(words, expected.map(|(word, ends)| (String::from(word), ends)));
+    }
+
+    #[test]
+    fn pages_end_at_a_sentence_past_the_shortest_length() {
+        let mut text = "word ".repeat(PAGE_WORDS + 5);
+        text.push_str("end. more words");
+        let passage = passage(&text);
+
+        assert_eq!(passage.page_end(0), PAGE_WORDS + 6);
+    }
+
+    #[test]
+    fn pages_without_a_sentence_end_stop_at_the_slack() {
+        let passage = passage(&"word ".repeat(PAGE_WORDS * 3));
+
+        assert_eq!(passage.page_end(0), PAGE_WORDS + PAGE_SLACK);
+        assert_eq!(passage.page_end(PAGE_WORDS * 3 - 10), PAGE_WORDS * 3);
+    }
+
+    #[test]
+    fn the_last_word_of_a_page_has_no_space() {
+        let passage = passage("One two three.");
+
+        let page: Vec<String> = passage.page(0, None).into_iter().map(|word| word.output).collect();
+        assert_eq!(page, vec!["One ", "two ", "three."]);
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     usize, vec,
 };
 
-use crate::statistics::get_letter_data;
+use crate::{statistics::get_letter_data, store::StatsStore};
 
 #[derive(Debug)]
 pub struct PlotData {
@@ -341,7 +341,7 @@ pub fn get_sin() -> String {
     get_plot(nums)
 }
 
-pub fn get_letter_plot(plot_data: PlotData) -> String {
+pub fn get_letter_plot(store: &mut dyn StatsStore, plot_data: PlotData) -> String {
     let is_wpm = {
         if plot_data.letter.is_none() {
             if let PlotType::AllWpm = plot_data.plot_type {
@@ -359,7 +359,7 @@ pub fn get_letter_plot(plot_data: PlotData) -> String {
     };
 
     let letter = plot_data.letter.unwrap_or(String::from("all"));
-    let nums = get_letter_data(&letter, is_wpm);
+    let nums = get_letter_data(store, &letter, is_wpm);
 
     get_plot(nums)
 }
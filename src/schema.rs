@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use json::{JsonValue, object};
+
+use crate::session_log::LOG_FILE;
+use crate::statistics::{lock_data, write_atomically};
+
+/// The layout of letter_data.json written by this version. Files without a version field are
+/// version 1, the layout from before there were versions.
+pub const SCHEMA_VERSION: u64 = 3;
+
+/// Turns a file of one version into the next, given the path of the file. The one at index 0
+/// goes from version 1 to 2.
+type Migration = fn(JsonValue, &Path) -> Result<JsonValue, io::Error>;
+const MIGRATIONS: [Migration; 2] = [migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// An empty file in the current layout.
+pub fn empty() -> JsonValue {
+    object! { version: SCHEMA_VERSION, legacy: {} }
+}
+
+/// Version 1 kept one `attempts` array per letter, `{ all: { attempts: [] }, a: { ... } }`, and
+/// since session records came along also a `sessions` array next to them. The attempt arrays
+/// cannot be matched up with sessions, so they are kept as they are under `legacy`.
+fn migrate_v1_to_v2(old: JsonValue, _path: &Path) -> Result<JsonValue, io::Error> {
+    let mut new = object! { version: 2, legacy: {}, sessions: [] };
+    for (key, value) in old.entries() {
+        match key {
+            "sessions" => new["sessions"] = value.clone(),
+            _ => new["legacy"][key] = value.clone(),
+        }
+    }
+    Ok(new)
+}
+
+/// Version 2 kept the sessions in a `sessions` array, so the whole file was rewritten for every
+/// lesson. They are moved to the session log next to it, which sessions are appended to.
+fn migrate_v2_to_v3(mut old: JsonValue, path: &Path) -> Result<JsonValue, io::Error> {
+    if !old["sessions"].is_array() {
+        return Err(corrupt(path, "its sessions are not a list"));
+    }
+
+    let log_path = path.with_file_name(LOG_FILE);
+    let _lock = lock_data(&log_path)?;
+
+    // A log that already exists was written by an earlier upgrade that stopped before saving
+    // letter_data.json, so the sessions are in it already.
+    if !log_path.exists() {
+        let mut lines = String::new();
+        for session in old["sessions"].members() {
+            lines.push_str(&session.dump());
+            lines.push('\n');
+        }
+        write_atomically(&log_path, lines.as_bytes())?;
+    }
+
+    old.remove("sessions");
+    old["version"] = 3.into();
+    Ok(old)
+}
+
+/// The layout version of the file, 1 when it has no version field. Anything but a whole number
+/// from 1 up is an error, as there is no layout to read it as.
+fn version_of(path: &Path, content: &JsonValue) -> Result<u64, io::Error> {
+    let version = &content["version"];
+    if version.is_null() {
+        return Ok(1);
+    }
+    match version.as_u64() {
+        Some(number) if number >= 1 => Ok(number),
+        _ => Err(corrupt(path, &format!("its layout version {version} is not a whole number from 1 up"))),
+    }
+}
+
+fn corrupt(path: &Path, problem: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "The statistics file {} could not be read: {problem}. It has been left as it is. Fix it, \
+             or move it away to start over with empty statistics. Backups made before upgrades are \
+             next to it.",
+            path.display(),
+        ),
+    )
+}
+
+/// Parses the statistics file at `path` and brings it up to `SCHEMA_VERSION`. The file is copied
+/// to a backup before it is upgraded, and the upgraded content is returned for the caller to save,
+/// with true when it was upgraded.
+pub fn load(path: &Path, text: &str) -> Result<(JsonValue, bool), io::Error> {
+    let mut content = json::parse(text).map_err(|err| corrupt(path, &err.to_string()))?;
+    if !content.is_object() {
+        return Err(corrupt(path, "it does not hold a JSON object"));
+    }
+
+    let version = version_of(path, &content)?;
+    if version > SCHEMA_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "The statistics file {} was written by a newer velogos (layout version {version}). \
+                 Update velogos to use it.",
+                path.display(),
+            ),
+        ));
+    }
+
+    let upgraded = version < SCHEMA_VERSION;
+    if upgraded {
+        backup(path, version)?;
+        for migration in &MIGRATIONS[(version - 1) as usize..] {
+            content = migration(content, path)?;
+        }
+    }
+
+    if !content["legacy"].is_object() {
+        return Err(corrupt(path, "its legacy attempts are not an object"));
+    }
+    Ok((content, upgraded))
+}
+
+/// Copies the file next to itself, named after the version it had and when it was copied.
+fn backup(path: &Path, version: u64) -> Result<(), io::Error> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("letter_data.json");
+    let backup_path = path.with_file_name(format!("{file_name}.v{version}-{timestamp}.bak"));
+    fs::copy(path, &backup_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    /// A directory for one test, with the path letter_data.json would have in it.
+    fn scratch_path(test: &str) -> (ScratchDir, PathBuf) {
+        let dir = ScratchDir::new(&format!("schema-{test}"));
+        let path = dir.path().join("letter_data.json");
+        (dir, path)
+    }
+
+    fn files_in(path: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn migrates_version_1_to_current() {
+        let (_dir, path) = scratch_path("v1");
+        let text = r#"{
+            "all": { "attempts": [{ "acc": 90, "wpm": 40 }] },
+            "a": { "attempts": [{ "acc": 100, "wpm": 50 }] },
+            "sessions": [{ "timestamp": 1, "all": { "acc": 95, "wpm": 45 } }, { "timestamp": 2 }]
+        }"#;
+        fs::write(&path, text).unwrap();
+
+        let (content, upgraded) = load(&path, text).unwrap();
+
+        assert!(upgraded);
+        assert_eq!(content["version"], SCHEMA_VERSION);
+        assert_eq!(content["legacy"]["all"]["attempts"][0]["wpm"], 40);
+        assert_eq!(content["legacy"]["a"]["attempts"][0]["acc"], 100);
+        assert!(content["sessions"].is_null());
+
+        let log = fs::read_to_string(path.with_file_name(LOG_FILE)).unwrap();
+        let timestamps: Vec<u64> = log.lines().map(|line| json::parse(line).unwrap()["timestamp"].as_u64().unwrap()).collect();
+        assert_eq!(timestamps, vec![1, 2]);
+        assert!(files_in(&path).iter().any(|name| name.starts_with("letter_data.json.v1-") && name.ends_with(".bak")));
+    }
+
+    #[test]
+    fn loads_current_version_as_it_is() {
+        let (_dir, path) = scratch_path("current");
+        let text = empty().dump();
+
+        let (content, upgraded) = load(&path, &text).unwrap();
+
+        assert!(!upgraded);
+        assert_eq!(content, empty());
+        assert!(files_in(&path).is_empty());
+    }
+
+    #[test]
+    fn rejects_version_0_without_a_backup() {
+        let (_dir, path) = scratch_path("v0");
+        let text = r#"{ "version": 0 }"#;
+        fs::write(&path, text).unwrap();
+
+        let err = load(&path, text).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(files_in(&path), vec!["letter_data.json"]);
+    }
+
+    #[test]
+    fn rejects_versions_that_are_not_whole_numbers() {
+        let (_dir, path) = scratch_path("fraction");
+        for text in [r#"{ "version": 1.5 }"#, r#"{ "version": "2" }"#, r#"{ "version": -1 }"#] {
+            assert_eq!(load(&path, text).unwrap_err().kind(), io::ErrorKind::InvalidData, "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let (_dir, path) = scratch_path("newer");
+        let text = format!(r#"{{ "version": {}, "legacy": {{}} }}"#, SCHEMA_VERSION + 1);
+
+        let err = load(&path, &text).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("newer velogos"));
+    }
+}
@@ -0,0 +1,28 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// An empty directory for one test, removed again when the test is done with it.
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    pub fn new(test: &str) -> ScratchDir {
+        let path = std::env::temp_dir().join(format!("velogos-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        ScratchDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
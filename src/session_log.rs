@@ -0,0 +1,285 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use json::JsonValue;
+
+use crate::statistics::{lock_data, write_atomically};
+
+pub const LOG_FILE: &str = "sessions.jsonl";
+/// How much of the log is read at a time when reading it from the end.
+const TAIL_CHUNK: u64 = 64 * 1024;
+
+/// The result for `key`, "all" or a letter, in a session. Null if the key was not typed in it.
+pub fn attempt_in<'a>(session: &'a JsonValue, key: &str) -> &'a JsonValue {
+    if key == "all" { &session["all"] } else { &session["letters"][key] }
+}
+
+/// The session history, one JSON record per line, oldest first. Sessions are only ever appended,
+/// so saving one costs the same however long the history is. The log is kept in memory with an
+/// index from every letter to the sessions it was typed in, and later reads only read the lines
+/// appended since. Compacting the log only drops lines that are not a session, like
+/// one cut short by a crash. Every session is kept, so the log grows with the history.
+pub struct SessionLog {
+    path: PathBuf,
+    /// How much of the file has been read, always up to the end of a whole line.
+    offset: u64,
+    sessions: Vec<JsonValue>,
+    by_letter: HashMap<String, Vec<usize>>,
+    /// Lines that were not a session, like one cut short by a crash.
+    broken_lines: usize,
+}
+
+impl SessionLog {
+    pub fn new(path: PathBuf) -> SessionLog {
+        SessionLog { path, offset: 0, sessions: vec![], by_letter: HashMap::new(), broken_lines: 0 }
+    }
+
+    /// Reads the lines appended since the last read. A last line without a newline is still being
+    /// written, or was cut short, and is left for later.
+    pub fn refresh(&mut self) -> Result<(), io::Error> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        // The log was compacted by another velogos, so it is read again from the start.
+        if file.metadata()?.len() < self.offset {
+            *self = SessionLog::new(self.path.clone());
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut new_bytes = vec![];
+        file.read_to_end(&mut new_bytes)?;
+        let Some(end) = new_bytes.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(());
+        };
+
+        for line in String::from_utf8_lossy(&new_bytes[..=end]).lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match json::parse(line) {
+                Ok(session) if session.is_object() => self.add(session),
+                _ => self.broken_lines += 1,
+            }
+        }
+        self.offset += end as u64 + 1;
+        Ok(())
+    }
+
+    fn add(&mut self, session: JsonValue) {
+        let index = self.sessions.len();
+        for (letter, _) in session["letters"].entries() {
+            self.by_letter.entry(letter.to_string()).or_default().push(index);
+        }
+        self.sessions.push(session);
+    }
+
+    #[cfg(test)]
+    pub fn sessions(&self) -> &[JsonValue] {
+        &self.sessions
+    }
+
+    /// The result for `key`, "all" or a letter, from every session it has one in.
+    pub fn attempts(&self, key: &str) -> Vec<JsonValue> {
+        if key == "all" {
+            return self.sessions.iter().map(|session| session["all"].clone()).filter(|all| !all.is_null()).collect();
+        }
+        let indices = self.by_letter.get(key).map(|indices| indices.as_slice()).unwrap_or(&[]);
+        indices.iter().map(|index| self.sessions[*index]["letters"][key].clone()).collect()
+    }
+
+    /// The last `count` sessions `wanted` accepts, oldest first. They are read straight from the
+    /// end of the file, without loading the log, so this takes the same time however long the
+    /// history is as long as the wanted sessions are recent.
+    pub fn latest(&self, count: usize, wanted: impl Fn(&JsonValue) -> bool) -> Result<Vec<JsonValue>, io::Error> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut found = vec![];
+        // The bytes from `start` that are not looked at yet, always ending after a whole line.
+        let mut buffer: Vec<u8> = vec![];
+        let mut start = file.metadata()?.len();
+        let mut trimmed = false;
+
+        while found.len() < count {
+            if start > 0 {
+                let chunk_start = start.saturating_sub(TAIL_CHUNK);
+                let mut chunk = vec![0u8; (start - chunk_start) as usize];
+                file.seek(SeekFrom::Start(chunk_start))?;
+                file.read_exact(&mut chunk)?;
+                chunk.extend_from_slice(&buffer);
+                buffer = chunk;
+                start = chunk_start;
+            }
+
+            // A last line without a newline is still being written, or was cut short.
+            if !trimmed {
+                match buffer.iter().rposition(|byte| *byte == b'\n') {
+                    Some(end) => {
+                        buffer.truncate(end + 1);
+                        trimmed = true;
+                    },
+                    None if start == 0 => break,
+                    None => continue,
+                }
+            }
+
+            // Takes whole lines off the end. The first line is only known to be whole once the
+            // start of the file is read.
+            while let Some(line_end) = buffer.len().checked_sub(1) {
+                let line_start = match buffer[..line_end].iter().rposition(|byte| *byte == b'\n') {
+                    Some(newline) => newline + 1,
+                    None if start == 0 => 0,
+                    None => break,
+                };
+                if let Ok(session) = json::parse(&String::from_utf8_lossy(&buffer[line_start..line_end]))
+                    && session.is_object()
+                    && wanted(&session)
+                {
+                    found.push(session);
+                }
+                buffer.truncate(line_start);
+                if found.len() == count {
+                    break;
+                }
+            }
+
+            if start == 0 {
+                break;
+            }
+        }
+
+        found.reverse();
+        Ok(found)
+    }
+
+    /// Every letter typed in some session.
+    pub fn letters(&self) -> impl Iterator<Item = &String> {
+        self.by_letter.keys()
+    }
+
+    pub fn needs_compaction(&self) -> bool {
+        self.broken_lines > 0
+    }
+
+    /// Adds a session to the end of the log in one write, synced to disk before returning.
+    pub fn append(&mut self, session: &JsonValue) -> Result<(), io::Error> {
+        let _lock = lock_data(&self.path)?;
+        let mut file = File::options().create(true).read(true).append(true).open(&self.path)?;
+
+        let mut line = String::new();
+        // A line cut short by a crash would run into this one without a newline between them.
+        if file.metadata()?.len() > 0 {
+            let mut last_byte = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last_byte)?;
+            if last_byte[0] != b'\n' {
+                line.push('\n');
+            }
+        }
+        line.push_str(&session.dump());
+        line.push('\n');
+
+        file.write_all(line.as_bytes())?;
+        file.sync_all()
+    }
+
+    /// Writes the log again with only the sessions that could be read, dropping broken lines.
+    pub fn compact(&mut self) -> Result<(), io::Error> {
+        let _lock = lock_data(&self.path)?;
+        *self = SessionLog::new(self.path.clone());
+        self.refresh()?;
+
+        let mut content = String::new();
+        for session in &self.sessions {
+            content.push_str(&session.dump());
+            content.push('\n');
+        }
+        write_atomically(&self.path, content.as_bytes())?;
+        self.offset = content.len() as u64;
+        self.broken_lines = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use json::object;
+
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    fn scratch_log(test: &str) -> (ScratchDir, SessionLog) {
+        let dir = ScratchDir::new(&format!("log-{test}"));
+        let log = SessionLog::new(dir.path().join(LOG_FILE));
+        (dir, log)
+    }
+
+    fn timestamps(sessions: &[JsonValue]) -> Vec<u64> {
+        sessions.iter().map(|session| session["timestamp"].as_u64().unwrap()).collect()
+    }
+
+    #[test]
+    fn latest_reads_from_the_end_oldest_first() {
+        let (_dir, mut log) = scratch_log("latest");
+        for timestamp in 0..5 {
+            log.append(&object! { timestamp: timestamp }).unwrap();
+        }
+
+        assert_eq!(timestamps(&log.latest(3, |_| true).unwrap()), vec![2, 3, 4]);
+        assert_eq!(timestamps(&log.latest(10, |_| true).unwrap()), vec![0, 1, 2, 3, 4]);
+        let even = log.latest(2, |session| session["timestamp"].as_u64().unwrap() % 2 == 0).unwrap();
+        assert_eq!(timestamps(&even), vec![2, 4]);
+    }
+
+    #[test]
+    fn latest_reads_across_chunks() {
+        let (_dir, mut log) = scratch_log("chunks");
+        let padding = "x".repeat(1000);
+        let mut content = String::new();
+        for timestamp in 0..200 {
+            content.push_str(&object! { timestamp: timestamp, padding: padding.as_str() }.dump());
+            content.push('\n');
+        }
+        fs::write(&log.path, content).unwrap();
+
+        assert_eq!(timestamps(&log.latest(150, |_| true).unwrap()), (50..200).collect::<Vec<u64>>());
+        log.refresh().unwrap();
+        assert_eq!(log.sessions().len(), 200);
+    }
+
+    #[test]
+    fn cut_short_and_broken_lines_are_skipped() {
+        let (_dir, mut log) = scratch_log("broken");
+        fs::write(&log.path, "{\"timestamp\":0}\nnot json\n{\"timestamp\":1}\n{\"timestamp\":2,\"al").unwrap();
+
+        assert_eq!(timestamps(&log.latest(5, |_| true).unwrap()), vec![0, 1]);
+        log.refresh().unwrap();
+        assert_eq!(timestamps(log.sessions()), vec![0, 1]);
+        assert!(log.needs_compaction());
+    }
+
+    #[test]
+    fn append_after_a_cut_short_line_starts_a_new_line() {
+        let (_dir, mut log) = scratch_log("append");
+        fs::write(&log.path, "{\"timestamp\":0}\n{\"timestamp\":1,\"al").unwrap();
+
+        log.append(&object! { timestamp: 2 }).unwrap();
+        log.refresh().unwrap();
+        log.compact().unwrap();
+
+        assert_eq!(timestamps(log.sessions()), vec![0, 2]);
+        assert_eq!(fs::read_to_string(&log.path).unwrap(), "{\"timestamp\":0}\n{\"timestamp\":2}\n");
+    }
+}
@@ -0,0 +1,391 @@
+use std::{io, path::Path, time::Duration};
+
+use json::{JsonValue, object};
+use rusqlite::{Connection, Row, TransactionBehavior, params};
+
+use crate::json_store::JsonStore;
+use crate::store::StatsStore;
+
+pub const DATABASE_FILE: &str = "statistics.sqlite3";
+/// The layout of the tables, kept in the user_version of the database. 0 is a new database.
+const DATABASE_VERSION: i64 = 2;
+/// How long to wait for another velogos that is writing to the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const CREATE_TABLES: &str = "
+    CREATE TABLE sessions (
+        id INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        mode TEXT NOT NULL,
+        source TEXT,
+        wpm REAL,
+        raw_wpm REAL,
+        accuracy REAL,
+        -- The whole session record as JSON, the same as in the session log.
+        record TEXT NOT NULL
+    );
+    -- How each key went in a session, one row for every key typed in it.
+    CREATE TABLE key_events (
+        session_id INTEGER NOT NULL REFERENCES sessions (id),
+        key TEXT NOT NULL,
+        accuracy REAL,
+        wpm REAL,
+        latency_ms REAL,
+        wrong_attempts INTEGER,
+        correction_ms REAL
+    );
+    CREATE INDEX key_events_by_key ON key_events (key, session_id);
+    -- Attempts saved before there was a session history, they belong to no session.
+    CREATE TABLE legacy_attempts (
+        id INTEGER PRIMARY KEY,
+        key TEXT NOT NULL,
+        accuracy REAL,
+        wpm REAL,
+        raw_wpm REAL
+    );
+    CREATE TABLE ngrams (
+        ngram TEXT PRIMARY KEY,
+        count INTEGER NOT NULL,
+        errors INTEGER NOT NULL,
+        timed INTEGER NOT NULL,
+        time_ms REAL NOT NULL
+    );
+";
+
+/// The statistics in a SQLite database in the data directory, next to the JSON files.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+fn sql_error(err: rusqlite::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it if it does not exist. A new database starts out
+    /// with everything in `json`, so no history is lost by switching store.
+    pub fn open(path: &Path, json: &mut JsonStore) -> Result<SqliteStore, io::Error> {
+        let mut connection = Connection::open(path).map_err(sql_error)?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(sql_error)?;
+
+        // Immediate, so two velogos opening a new database at once do not both fill it.
+        let transaction = connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(sql_error)?;
+        let version: i64 = transaction
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(sql_error)?;
+
+        if version > DATABASE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The statistics database {} was written by a newer velogos (layout version {version}). \
+                     Update velogos to use it.",
+                    path.display(),
+                ),
+            ));
+        }
+        if version == 0 {
+            transaction.execute_batch(CREATE_TABLES).map_err(sql_error)?;
+            import(&transaction, json)?;
+        } else if version == 1 {
+            transaction
+                .execute_batch("ALTER TABLE key_events ADD COLUMN correction_ms REAL")
+                .map_err(sql_error)?;
+        }
+        if version < DATABASE_VERSION {
+            transaction.pragma_update(None, "user_version", DATABASE_VERSION).map_err(sql_error)?;
+        }
+        transaction.commit().map_err(sql_error)?;
+
+        Ok(SqliteStore { connection })
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> SqliteStore {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(CREATE_TABLES).unwrap();
+        SqliteStore { connection }
+    }
+}
+
+/// Copies the statistics in the JSON files into a new database.
+fn import(connection: &Connection, json: &mut JsonStore) -> Result<(), io::Error> {
+    for (key, value) in json.legacy_attempts().entries() {
+        for attempt in value["attempts"].members() {
+            connection
+                .execute(
+                    "INSERT INTO legacy_attempts (key, accuracy, wpm, raw_wpm) VALUES (?1, ?2, ?3, ?4)",
+                    params![key, attempt["acc"].as_f64(), attempt["wpm"].as_f64(), attempt["raw_wpm"].as_f64()],
+                )
+                .map_err(sql_error)?;
+        }
+    }
+    for session in json.latest_sessions(usize::MAX)? {
+        insert_session(connection, &session).map_err(sql_error)?;
+    }
+    add_ngrams(connection, &json.ngram_totals()?).map_err(sql_error)
+}
+
+fn insert_session(connection: &Connection, session: &JsonValue) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO sessions (timestamp, mode, source, wpm, raw_wpm, accuracy, record)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            session["timestamp"].as_i64().unwrap_or(0),
+            session["mode"].as_str().unwrap_or("?"),
+            session["source"].as_str(),
+            session["all"]["wpm"].as_f64(),
+            session["all"]["raw_wpm"].as_f64(),
+            session["all"]["acc"].as_f64(),
+            session.dump(),
+        ],
+    )?;
+
+    let session_id = connection.last_insert_rowid();
+    let mut insert_event = connection.prepare_cached(
+        "INSERT INTO key_events (session_id, key, accuracy, wpm, latency_ms, wrong_attempts, correction_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?;
+    for (key, result) in session["letters"].entries() {
+        insert_event.execute(params![
+            session_id,
+            key,
+            result["acc"].as_f64(),
+            result["wpm"].as_f64(),
+            result["latency_ms"].as_f64(),
+            result["wrong_attempts"].as_i64(),
+            result["correction_ms"].as_f64(),
+        ])?;
+    }
+    Ok(())
+}
+
+fn add_ngrams(connection: &Connection, lesson: &JsonValue) -> rusqlite::Result<()> {
+    let mut upsert = connection.prepare_cached(
+        "INSERT INTO ngrams (ngram, count, errors, timed, time_ms) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (ngram) DO UPDATE SET
+             count = count + excluded.count,
+             errors = errors + excluded.errors,
+             timed = timed + excluded.timed,
+             time_ms = time_ms + excluded.time_ms",
+    )?;
+    for (ngram, result) in lesson.entries() {
+        upsert.execute(params![
+            ngram,
+            result["count"].as_i64().unwrap_or(0),
+            result["errors"].as_i64().unwrap_or(0),
+            result["timed"].as_i64().unwrap_or(0),
+            result["time_ms"].as_f64().unwrap_or(0.0),
+        ])?;
+    }
+    Ok(())
+}
+
+fn summary_attempt(row: &Row) -> rusqlite::Result<JsonValue> {
+    Ok(object! {
+        acc: row.get::<_, Option<f64>>(0)?,
+        wpm: row.get::<_, Option<f64>>(1)?,
+        raw_wpm: row.get::<_, Option<f64>>(2)?,
+    })
+}
+
+fn key_attempt(row: &Row) -> rusqlite::Result<JsonValue> {
+    Ok(object! {
+        acc: row.get::<_, Option<f64>>(0)?,
+        wpm: row.get::<_, Option<f64>>(1)?,
+        latency_ms: row.get::<_, Option<f64>>(2)?,
+        wrong_attempts: row.get::<_, Option<i64>>(3)?,
+        correction_ms: row.get::<_, Option<f64>>(4)?,
+    })
+}
+
+/// The latest `limit` attempts at `key`, oldest first, or every attempt with a limit of -1.
+/// Attempts saved before there was a session history are older than any session, so they are
+/// only read when the sessions do not have enough.
+fn read_attempts(connection: &Connection, key: &str, limit: i64) -> rusqlite::Result<Vec<JsonValue>> {
+    let mut attempts = vec![];
+
+    if key == "all" {
+        let mut sessions = connection.prepare("SELECT accuracy, wpm, raw_wpm FROM sessions ORDER BY id DESC LIMIT ?1")?;
+        for attempt in sessions.query_map([limit], summary_attempt)? {
+            attempts.push(attempt?);
+        }
+    } else {
+        let mut events = connection.prepare(
+            "SELECT accuracy, wpm, latency_ms, wrong_attempts, correction_ms FROM key_events WHERE key = ?1
+             ORDER BY session_id DESC LIMIT ?2",
+        )?;
+        for attempt in events.query_map(params![key, limit], key_attempt)? {
+            attempts.push(attempt?);
+        }
+    }
+
+    let remaining = if limit < 0 { -1 } else { limit - attempts.len() as i64 };
+    if remaining != 0 {
+        let mut legacy = connection
+            .prepare("SELECT accuracy, wpm, raw_wpm FROM legacy_attempts WHERE key = ?1 ORDER BY id DESC LIMIT ?2")?;
+        for attempt in legacy.query_map(params![key, remaining], summary_attempt)? {
+            attempts.push(attempt?);
+        }
+    }
+
+    attempts.reverse();
+    Ok(attempts)
+}
+
+impl StatsStore for SqliteStore {
+    fn add_session(&mut self, session: &JsonValue) -> Result<(), io::Error> {
+        let transaction = self.connection.transaction().map_err(sql_error)?;
+        insert_session(&transaction, session).map_err(sql_error)?;
+        transaction.commit().map_err(sql_error)
+    }
+
+    fn latest_sessions(&mut self, count: usize) -> Result<Vec<JsonValue>, io::Error> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT record FROM (SELECT id, record FROM sessions ORDER BY id DESC LIMIT ?1) ORDER BY id")
+            .map_err(sql_error)?;
+        let records = statement
+            .query_map([i64::try_from(count).unwrap_or(i64::MAX)], |row| row.get::<_, String>(0))
+            .map_err(sql_error)?;
+
+        let mut sessions = vec![];
+        for record in records {
+            let session = json::parse(&record.map_err(sql_error)?)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+            sessions.push(session);
+        }
+        Ok(sessions)
+    }
+
+    fn attempts(&mut self, key: &str) -> Result<JsonValue, io::Error> {
+        Ok(JsonValue::Array(read_attempts(&self.connection, key, -1).map_err(sql_error)?))
+    }
+
+    fn recent_attempts(&mut self, key: &str, count: usize) -> Result<JsonValue, io::Error> {
+        let limit = i64::try_from(count).unwrap_or(i64::MAX);
+        Ok(JsonValue::Array(read_attempts(&self.connection, key, limit).map_err(sql_error)?))
+    }
+
+    fn tracked_keys(&mut self) -> Result<Vec<String>, io::Error> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT key FROM legacy_attempts UNION SELECT key FROM key_events")
+            .map_err(sql_error)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(sql_error)?;
+
+        let mut keys = vec![String::from("all")];
+        for key in rows {
+            keys.push(key.map_err(sql_error)?);
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn add_ngrams(&mut self, lesson: &JsonValue) -> Result<(), io::Error> {
+        let transaction = self.connection.transaction().map_err(sql_error)?;
+        add_ngrams(&transaction, lesson).map_err(sql_error)?;
+        transaction.commit().map_err(sql_error)
+    }
+
+    fn ngram_totals(&mut self) -> Result<JsonValue, io::Error> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT ngram, count, errors, timed, time_ms FROM ngrams")
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                let totals = object! {
+                    count: row.get::<_, i64>(1)?,
+                    errors: row.get::<_, i64>(2)?,
+                    timed: row.get::<_, i64>(3)?,
+                    time_ms: row.get::<_, f64>(4)?,
+                };
+                Ok((row.get::<_, String>(0)?, totals))
+            })
+            .map_err(sql_error)?;
+
+        let mut content = JsonValue::new_object();
+        for row in rows {
+            let (ngram, totals) = row.map_err(sql_error)?;
+            content[ngram] = totals;
+        }
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(timestamp: u64, wpm: f64) -> JsonValue {
+        object! {
+            timestamp: timestamp,
+            mode: "words",
+            all: { acc: 90.0, wpm: wpm, raw_wpm: wpm + 1.0 },
+            letters: { a: { acc: 100.0, wpm: wpm, correction_ms: 250.0 } },
+        }
+    }
+
+    fn wpms(attempts: &JsonValue) -> Vec<f64> {
+        attempts.members().map(|attempt| attempt["wpm"].as_f64().unwrap()).collect()
+    }
+
+    #[test]
+    fn sessions_come_back_as_they_were_saved() {
+        let mut store = SqliteStore::in_memory();
+        let sessions: Vec<JsonValue> = (1..=3).map(|timestamp| session(timestamp, timestamp as f64 * 10.0)).collect();
+        for session in &sessions {
+            store.add_session(session).unwrap();
+        }
+
+        assert_eq!(store.latest_sessions(10).unwrap(), sessions);
+        assert_eq!(store.latest_sessions(1).unwrap(), vec![sessions[2].clone()]);
+    }
+
+    #[test]
+    fn recent_attempts_are_the_latest_oldest_first() {
+        let mut store = SqliteStore::in_memory();
+        for timestamp in 1..=5 {
+            store.add_session(&session(timestamp, timestamp as f64)).unwrap();
+        }
+
+        assert_eq!(wpms(&store.recent_attempts("a", 2).unwrap()), vec![4.0, 5.0]);
+        assert_eq!(wpms(&store.recent_attempts("all", 3).unwrap()), vec![3.0, 4.0, 5.0]);
+        assert_eq!(wpms(&store.attempts("a").unwrap()), vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(store.recent_attempts("a", 1).unwrap()[0]["correction_ms"], 250.0);
+    }
+
+    #[test]
+    fn legacy_attempts_come_before_the_sessions() {
+        let mut store = SqliteStore::in_memory();
+        for wpm in [0.1, 0.2] {
+            store
+                .connection
+                .execute("INSERT INTO legacy_attempts (key, accuracy, wpm, raw_wpm) VALUES ('a', 100, ?1, NULL)", [wpm])
+                .unwrap();
+        }
+        store.add_session(&session(1, 1.0)).unwrap();
+
+        assert_eq!(wpms(&store.attempts("a").unwrap()), vec![0.1, 0.2, 1.0]);
+        assert_eq!(wpms(&store.recent_attempts("a", 2).unwrap()), vec![0.2, 1.0]);
+        assert_eq!(wpms(&store.recent_attempts("a", 1).unwrap()), vec![1.0]);
+        assert_eq!(store.tracked_keys().unwrap(), vec!["a", "all"]);
+    }
+
+    #[test]
+    fn ngrams_are_added_up() {
+        let mut store = SqliteStore::in_memory();
+        let lesson = object! { th: { count: 2, errors: 1, timed: 2, time_ms: 300.0 } };
+        store.add_ngrams(&lesson).unwrap();
+        store.add_ngrams(&lesson).unwrap();
+
+        let totals = store.ngram_totals().unwrap();
+        assert_eq!(totals["th"]["count"], 4);
+        assert_eq!(totals["th"]["errors"], 2);
+        assert_eq!(totals["th"]["time_ms"], 600.0);
+    }
+}
@@ -3,36 +3,186 @@ use std::fs::File;
 use std::io;
 use std::fs;
 use std::io::Write;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use directories::ProjectDirs;
 use json::object;
 use json::JsonValue;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::command_line::StatsView;
+use crate::json_store::JsonStore;
+use crate::schema;
+use crate::session_log;
+use crate::sqlite_store::{self, SqliteStore};
+use crate::store::{StatsStore, StoreKind};
+use crate::word_tree::{Letter, Word};
+
+const STATS_COLUMNS: usize = 13;
+const SESSION_ROWS: usize = 10;
+const LETTER_FILE: &str = "letter_data.json";
+const NGRAM_FILE: &str = "ngram_data.json";
+const TEXT_PROGRESS_FILE: &str = "text_progress.json";
+/// N-grams seen fewer times than this are left out of rankings, they are mostly noise.
+const MIN_NGRAM_COUNT: f64 = 5.0;
+const NGRAM_TABLE_ROWS: usize = 10;
+const RECENT_ATTEMPTS: usize = 10;
+const SLOWEST_WORDS: usize = 3;
+const MISSED_LETTERS: usize = 5;
+
+pub fn write_json_to_file(path: &Path, json_to_write: JsonValue) -> Result<(), io::Error> {
+    write_atomically(path, json::stringify(json_to_write).as_bytes())
+}
 
-use crate::word_tree::Word;
+/// Writes to a synced temporary file and renames it over `path`, so a crash leaves either the
+/// old or the new file.
+pub fn write_atomically(path: &Path, content: &[u8]) -> Result<(), io::Error> {
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("data");
+    let temp_path = path.with_file_name(format!(".{file_name}.tmp-{}", std::process::id()));
 
-pub const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz";
+    let res = write_synced(&temp_path, content)
+        .and_then(|_| fs::rename(&temp_path, path));
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return res;
+    }
 
-fn write_json_to_file(json_to_write: JsonValue) -> Result<(), io::Error> {
-    let path = get_stats_path();
+    // The rename is only durable once the directory is synced. Directories can not be opened
+    // for this everywhere, and the data itself is safe by now, so a failure here is ignored.
+    if let Some(dir) = path.parent()
+        && let Ok(dir) = File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn write_synced(path: &Path, content: &[u8]) -> Result<(), io::Error> {
     let mut file = File::create(path)?;
-    let stringifyed_json = json::stringify(json_to_write);
-    file.write_all(stringifyed_json.as_bytes())?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// The lock is on a separate file, as the data file is replaced on every write. It is released
+/// when the returned file is dropped.
+pub fn lock_data(path: &Path) -> Result<File, io::Error> {
+    let lock = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+fn get_stats_path() -> PathBuf {
+    get_data_path(LETTER_FILE, schema::empty)
+}
+
+/// An older file is upgraded and saved first. A broken one is left as it is, with an error.
+pub fn load_stats() -> Result<JsonValue, io::Error> {
+    let path = get_stats_path();
+    let _lock = lock_data(&path)?;
+    read_stats(&path)
+}
+
+/// Like `load_stats`, for callers that already hold the lock.
+fn read_stats(path: &PathBuf) -> Result<JsonValue, io::Error> {
+    let text = read_file_as_string(path)?;
+    let (content, upgraded) = schema::load(path, &text)?;
+    if upgraded {
+        write_json_to_file(path, content.clone())?;
+    }
+    Ok(content)
+}
+
+/// Sessions saved with the other store since the last one in this store are copied over first.
+pub fn open_store(kind: StoreKind) -> Result<Box<dyn StatsStore>, io::Error> {
+    let mut json = JsonStore::open(get_data_dir().join(session_log::LOG_FILE))?;
+    let database_path = get_data_dir().join(sqlite_store::DATABASE_FILE);
+
+    match kind {
+        StoreKind::Json => {
+            if database_path.exists() {
+                let res = SqliteStore::open(&database_path, &mut json)
+                    .and_then(|mut sqlite| copy_new_sessions(&mut sqlite, &mut json));
+                if let Err(err) = res {
+                    println!("Could not bring over the sessions saved with --store sqlite: {}", err);
+                }
+            }
+            Ok(Box::new(json))
+        },
+        StoreKind::Sqlite => {
+            let mut sqlite = SqliteStore::open(&database_path, &mut json)?;
+            if let Err(err) = copy_new_sessions(&mut json, &mut sqlite) {
+                println!("Could not bring over the sessions saved with --store json: {}", err);
+            }
+            Ok(Box::new(sqlite))
+        },
+    }
+}
+
+fn session_timestamp(session: &JsonValue) -> u64 {
+    session["timestamp"].as_u64().unwrap_or(0)
+}
+
+/// Reads back from the latest session only as far as `timestamp`.
+fn sessions_since(store: &mut dyn StatsStore, timestamp: u64) -> Result<Vec<JsonValue>, io::Error> {
+    let mut count = 16;
+    loop {
+        let sessions = store.latest_sessions(count)?;
+        let reaches_back = sessions.first().is_none_or(|session| session_timestamp(session) < timestamp);
+        if sessions.len() < count || reaches_back {
+            return Ok(sessions.into_iter().filter(|session| session_timestamp(session) >= timestamp).collect());
+        }
+        count *= 2;
+    }
+}
+
+/// Timestamps are whole seconds, so sessions from the last second in `to` are compared to skip
+/// copies.
+fn copy_new_sessions(from: &mut dyn StatsStore, to: &mut dyn StatsStore) -> Result<(), io::Error> {
+    let since = to.latest_sessions(1)?.first().map(session_timestamp).unwrap_or(0);
+    let known: HashSet<String> = sessions_since(to, since)?.iter().map(JsonValue::dump).collect();
+
+    for session in sessions_since(from, since)? {
+        if !known.contains(&session.dump()) {
+            to.add_session(&session)?;
+        }
+    }
     Ok(())
 }
 
-fn get_empty_json() -> JsonValue {
-    let mut json = object! { all: { attempts: [] } };
-    for letter in ALPHABET.chars() {
-        let inner_json = object! {
-            attempts: [],
-        };
-        json[letter.to_string()] = inner_json;
+pub fn get_ngram_path() -> PathBuf {
+    get_data_path(NGRAM_FILE, JsonValue::new_object)
+}
+
+fn get_text_progress_path() -> PathBuf {
+    get_data_path(TEXT_PROGRESS_FILE, JsonValue::new_object)
+}
+
+fn get_data_path(file_name: &str, get_empty: fn() -> JsonValue) -> PathBuf {
+    let file_path = get_data_dir().join(file_name);
+
+    if !file_path.exists() {
+        // Checked again under the lock, another velogos could have made it in the meantime.
+        let res = lock_data(&file_path).and_then(|_lock| {
+            if file_path.exists() {
+                return Ok(());
+            }
+            write_json_to_file(&file_path, get_empty())
+        });
+
+        if res.is_err() {
+            println!("Could not write to file.");
+            std::process::exit(1);
+        }
     }
-    json
+
+    file_path
 }
 
-fn get_stats_path() -> PathBuf {
+fn get_data_dir() -> PathBuf {
     if let Some(dir) = ProjectDirs::from("org", "fritsvold", "velogos") {
 
         let dir_path: PathBuf = dir.data_dir().to_path_buf();
@@ -43,20 +193,7 @@ fn get_stats_path() -> PathBuf {
             std::process::exit(1);
         }
 
-        let file_path = dir_path.join("letter_data.json");
-        let new_file = File::create_new(&file_path);
-
-        if new_file.is_ok() {
-            let empty_json = get_empty_json();
-            let res = write_json_to_file(empty_json);
-
-            if res.is_err() {
-                println!("Could not write to file.");
-                std::process::exit(1);
-            }
-        }
-        
-        return file_path;
+        dir_path
     }
     else {
         println!("Could not find project directory path.");
@@ -64,33 +201,61 @@ fn get_stats_path() -> PathBuf {
     }
 }
 
-fn read_file_as_string() -> Result<String, io::Error> {
-    let path = get_stats_path();
+fn read_file_as_string(path: &PathBuf) -> Result<String, io::Error> {
     let content = read_to_string(path)?;
     Ok(content)
 }
-fn get_json_from_file() -> Result<JsonValue, io::Error> {
-    let string_content = read_file_as_string()?;
-    let content = json::parse(&string_content).expect("Failed to parse json.");
-    Ok(content)
+pub fn get_json_from_file(path: &PathBuf) -> Result<JsonValue, io::Error> {
+    let string_content = read_file_as_string(path)?;
+    json::parse(&string_content).map_err(|err| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{} is not valid JSON: {err}", path.display()))
+    })
 }
 
 fn calc_accuracy(words: &Vec<&Word>) -> f64 {
     
-    let length: usize = words.iter().map(|word| word.output.len()).sum();
+    let length: usize = words.iter().map(|word| word.letters.len()).sum();
     if length < 1 {
         return 0.0;
     }
     let count_correct_letters_in_word = 
         |word: &&Word| word.letters.iter().filter(|letter| letter.correct.unwrap_or(false) ).count();
-    let total_correct_letters: usize = words.iter().map(|word| count_correct_letters_in_word(word) ).sum();
+    let total_correct_letters: usize = words.iter().map(count_correct_letters_in_word).sum();
     let acc: f64 = (total_correct_letters as f64 / length as f64) * 100f64;
     acc
 }
 
+/// A wrong key is an error whether it was deleted again later or left in the text.
+fn calc_keystroke_accuracy(words: &[&Word]) -> f64 {
+    let letters = words.iter().flat_map(|word| word.letters.iter());
+    let (keystrokes, wrong) = letters.fold((0, 0), |(keystrokes, wrong), letter| {
+        (keystrokes + letter.keystrokes, wrong + letter.wrong_attempts)
+    });
+    if keystrokes == 0 {
+        return 0.0;
+    }
+    (keystrokes - wrong) as f64 / keystrokes as f64 * 100f64
+}
+
+/// The wrong keys that were deleted again and the ones still left in the text.
+fn count_errors(words: &[Word]) -> (u32, u32) {
+    let letters = words.iter().flat_map(|word| word.letters.iter());
+    let mut wrong = 0;
+    let mut uncorrected = 0;
+    for letter in letters {
+        wrong += letter.wrong_attempts;
+        if letter.input.is_some_and(|input| input != letter.letter) {
+            uncorrected += 1;
+        }
+    }
+    (wrong - uncorrected, uncorrected)
+}
+
+/// Only the letters of words typed without mistakes count, but the time of every word does, so
+/// this is never above the raw wpm.
 fn calc_wpm(words: &Vec<&Word>) -> f64 {
 
-    let words: Vec<&&Word> = words
+    let character_count: f64 = words
         .iter()
         .filter(
             |word| word.letters
@@ -99,9 +264,18 @@ fn calc_wpm(words: &Vec<&Word>) -> f64 {
                     |letter| letter.correct.unwrap_or(false)
                 )
         )
-        .collect();
+        .map(|word| word.letters.len() as f64)
+        .sum();
 
+    per_minute(character_count, words)
+}
+
+fn calc_raw_wpm(words: &[&Word]) -> f64 {
     let character_count: f64 = words.iter().map(|word| word.letters.len() as f64).sum();
+    per_minute(character_count, words)
+}
+
+fn per_minute(character_count: f64, words: &[&Word]) -> f64 {
 
     let all_words_durations = words.iter().map(|word| word.time);
 
@@ -119,72 +293,320 @@ fn calc_wpm(words: &Vec<&Word>) -> f64 {
         return 0.0;
     }
 
-    let wpm = (character_count / 5f64) / (total_duration.as_secs_f64() / 60f64);
-    wpm
+    (character_count / 5f64) / (total_duration.as_secs_f64() / 60f64)
 }
 
 
-pub fn add_new_result(words: Vec<Word>) {
+/// Every typed letter with the time since the letter before it.
+fn get_keystrokes(words: &[Word]) -> Vec<(&Letter, Option<Duration>)> {
+    let mut keystrokes = vec![];
+    let mut previous: Option<&Letter> = None;
+    for letter in words.iter().flat_map(|word| word.letters.iter()) {
+        let latency = previous.and_then(|previous| letter.latency(previous));
+        keystrokes.push((letter, latency));
+        previous = Some(letter);
+    }
+    keystrokes
+}
 
-    let all_acc = calc_accuracy(&words.iter().collect());
-    let all_wpm = calc_wpm(&words.iter().collect());
+/// Five keys count as a word.
+fn latency_to_wpm(latency_ms: f64) -> f64 {
+    if latency_ms <= 0.0 {
+        return 0.0;
+    }
+    60_000f64 / (latency_ms * 5f64)
+}
 
-    let mut new_json = object! {
-        all: { acc: all_acc, wpm: all_wpm }
+/// Both the saved result and the live status line come from here, so they always agree.
+#[derive(Clone, Copy)]
+pub struct Summary {
+    pub wpm: f64,
+    pub raw_wpm: f64,
+    pub accuracy: f64,
+}
+
+pub fn summarize(words: &Vec<&Word>, real_typing: bool) -> Summary {
+    let accuracy = if real_typing {
+        calc_keystroke_accuracy(words)
+    } else {
+        calc_accuracy(words)
     };
+    Summary { wpm: calc_wpm(words), raw_wpm: calc_raw_wpm(words), accuracy }
+}
 
-    for letter in ALPHABET.chars() {
-        let words_with_letter: Vec<&Word> = words.iter().filter(|word| word.output.contains(letter)).collect();
+pub struct Report {
+    pub summary: Summary,
+    pub word_count: usize,
+    /// 100% means every keystroke took the same time.
+    pub consistency: Option<f64>,
+    pub slowest_words: Vec<(String, f64)>,
+    pub missed_letters: Vec<(char, u32)>,
+    pub average_wpm: Option<f64>,
+    pub average_accuracy: Option<f64>,
+}
 
-        if words_with_letter.len() == 0 {
-            continue;
+fn calc_consistency(words: &[Word]) -> Option<f64> {
+    let latencies: Vec<f64> = get_keystrokes(words)
+        .iter()
+        .filter_map(|keystroke| keystroke.1)
+        .map(|latency| latency.as_secs_f64())
+        .collect();
+    if latencies.len() < 2 {
+        return None;
+    }
+
+    let mean = latencies.iter().sum::<f64>() / latencies.len() as f64;
+    if mean <= 0.0 {
+        return None;
+    }
+    let variance = latencies.iter().map(|latency| (latency - mean).powi(2)).sum::<f64>() / latencies.len() as f64;
+    Some((100f64 * (1f64 - variance.sqrt() / mean)).max(0.0))
+}
+
+fn find_slowest_words(words: &[Word]) -> Vec<(String, f64)> {
+    let mut speeds: Vec<(String, f64)> = words
+        .iter()
+        .filter(|word| !word.output.trim().is_empty())
+        .filter_map(|word| {
+            let wpm = calc_raw_wpm(&[word]);
+            if wpm > 0.0 {
+                Some((word.output.trim().to_string(), wpm))
+            } else {
+                None
+            }
+        })
+        .collect();
+    speeds.sort_by(|a, b| a.1.total_cmp(&b.1));
+    speeds.truncate(SLOWEST_WORDS);
+    speeds
+}
+
+fn find_missed_letters(words: &[Word]) -> Vec<(char, u32)> {
+    let mut missed: HashMap<char, u32> = HashMap::new();
+    for letter in words.iter().flat_map(|word| word.letters.iter()) {
+        if letter.wrong_attempts > 0 {
+            *missed.entry(letter.letter).or_insert(0) += letter.wrong_attempts;
         }
-        let letter_acc = calc_accuracy(&words_with_letter);
-        let letter_wpm = calc_wpm(&words_with_letter);
+    }
+
+    let mut missed: Vec<(char, u32)> = missed.into_iter().collect();
+    missed.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    missed.truncate(MISSED_LETTERS);
+    missed
+}
+
+fn average_recent(attempts: &JsonValue, key: &str) -> Option<f64> {
+    let values: Vec<f64> = attempts
+        .members()
+        .skip(attempts.len().saturating_sub(RECENT_ATTEMPTS))
+        .filter_map(|attempt| attempt[key].as_f64())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+fn make_report(store: &mut dyn StatsStore, words: &[Word], summary: Summary) -> Report {
+    let attempts = store.recent_attempts("all", RECENT_ATTEMPTS);
+    let (average_wpm, average_accuracy) = match attempts {
+        Ok(attempts) => (average_recent(&attempts, "wpm"), average_recent(&attempts, "acc")),
+        Err(_) => (None, None),
+    };
+
+    Report {
+        summary,
+        word_count: words.len(),
+        consistency: calc_consistency(words),
+        slowest_words: find_slowest_words(words),
+        missed_letters: find_missed_letters(words),
+        average_wpm,
+        average_accuracy,
+    }
+}
+
+fn against_average(value: f64, average: Option<f64>) -> String {
+    match average {
+        Some(average) => format!(" ({:+.1} against your average of {average:.1})", value - average),
+        None => String::new(),
+    }
+}
 
-        new_json[letter.to_string()] = object! {
+pub fn print_report(report: &Report) {
+    let summary = &report.summary;
+    println!("wpm:         {:.1}{}", summary.wpm, against_average(summary.wpm, report.average_wpm));
+    println!("raw wpm:     {:.1}", summary.raw_wpm);
+    println!("accuracy:    {:.1}%{}", summary.accuracy, against_average(summary.accuracy, report.average_accuracy));
+    match report.consistency {
+        Some(consistency) => println!("consistency: {consistency:.0}%"),
+        None => println!("consistency: N/A"),
+    }
+
+    if !report.slowest_words.is_empty() {
+        let slowest: Vec<String> = report.slowest_words
+            .iter()
+            .map(|(word, wpm)| format!("{word} ({wpm:.0} wpm)"))
+            .collect();
+        println!("slowest words: {}", slowest.join(", "));
+    }
+    if !report.missed_letters.is_empty() {
+        let missed: Vec<String> = report.missed_letters
+            .iter()
+            .map(|(letter, count)| match letter {
+                ' ' => format!("space {count}"),
+                '\t' => format!("tab {count}"),
+                '\n' => format!("enter {count}"),
+                _ => format!("{letter} {count}"),
+            })
+            .collect();
+        println!("most missed:   {}", missed.join(", "));
+    }
+}
+
+#[derive(Clone)]
+pub struct ResultInfo {
+    pub mode: String,
+    pub options: Vec<String>,
+    pub source: Option<String>,
+    /// None for texts typed in order.
+    pub seed: Option<u64>,
+    pub duration: Option<Duration>,
+    pub real_typing: bool,
+}
+
+fn average_ms(durations: &[Duration]) -> Option<f64> {
+    if durations.is_empty() {
+        return None;
+    }
+    Some(durations.iter().sum::<Duration>().as_secs_f64() * 1000f64 / durations.len() as f64)
+}
+
+/// Whitespace is left out.
+fn make_letter_breakdown(words: &[Word]) -> JsonValue {
+    let keystrokes = get_keystrokes(words);
+
+    let mut typed_letters: Vec<char> = keystrokes
+        .iter()
+        .map(|keystroke| keystroke.0.letter)
+        .filter(|letter| !letter.is_whitespace())
+        .collect();
+    typed_letters.sort();
+    typed_letters.dedup();
+
+    let mut letters = JsonValue::new_object();
+    for letter in typed_letters {
+        let letter_keystrokes: Vec<&(&Letter, Option<Duration>)> = keystrokes
+            .iter()
+            .filter(|keystroke| keystroke.0.letter == letter)
+            .collect();
+
+        let correct_count = letter_keystrokes.iter().filter(|keystroke| keystroke.0.correct.unwrap_or(false)).count();
+        let letter_acc = correct_count as f64 / letter_keystrokes.len() as f64 * 100f64;
+        let wrong_attempts: u32 = letter_keystrokes.iter().map(|keystroke| keystroke.0.wrong_attempts).sum();
+
+        let latencies: Vec<Duration> = letter_keystrokes.iter().filter_map(|keystroke| keystroke.1).collect();
+        let latency_ms = average_ms(&latencies);
+        let corrections: Vec<Duration> = letter_keystrokes.iter().filter_map(|keystroke| keystroke.0.correction_time()).collect();
+        let correction_ms = average_ms(&corrections);
+
+        letters[letter.to_string()] = object! {
             acc: letter_acc,
-            wpm: letter_wpm,
+            wpm: latency_ms.map(latency_to_wpm),
+            latency_ms: latency_ms,
+            wrong_attempts: wrong_attempts,
+            correction_ms: correction_ms,
         };
     }
-    let res = update_stats(new_json);
-    if res.is_err() {
-        println!("Failed to update json with this error: {:?}", res.err())
+    letters
+}
+
+fn make_session(words: &[Word], info: &ResultInfo, summary: &Summary) -> JsonValue {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+    let duration = info.duration.unwrap_or_else(|| words.iter().filter_map(|word| word.time).sum());
+    let letters = words.iter().flat_map(|word| word.letters.iter());
+    let keystrokes: u32 = letters.clone().map(|letter| letter.keystrokes).sum();
+    let errors: u32 = letters.map(|letter| letter.wrong_attempts).sum();
+
+    let mut session = object! {
+        timestamp: timestamp,
+        mode: info.mode.as_str(),
+        options: info.options.clone(),
+        source: info.source.clone(),
+        seed: info.seed,
+        real_typing: info.real_typing,
+        duration_ms: duration.as_millis() as u64,
+        word_count: words.len(),
+        keystrokes: keystrokes,
+        errors: errors,
+        all: { acc: summary.accuracy, wpm: summary.wpm, raw_wpm: summary.raw_wpm },
+        letters: make_letter_breakdown(words),
+    };
+    if info.real_typing {
+        let (corrected, uncorrected) = count_errors(words);
+        session["corrected_errors"] = corrected.into();
+        session["uncorrected_errors"] = uncorrected.into();
     }
+    session
 }
 
+/// The report is made before saving, so it compares against the earlier attempts only.
+pub fn add_new_result(store: &mut dyn StatsStore, words: Vec<Word>, info: ResultInfo) -> Report {
 
-#[allow(dead_code)]
-fn update_stats(new_json: JsonValue) -> Result<(), io::Error> {
-    let mut content = get_json_from_file()?;
+    let summary = summarize(&words.iter().collect(), info.real_typing);
+    let report = make_report(store, &words, summary);
 
-    for entry in new_json.entries() {
-        let letter = entry.0.to_string();
-        let value = entry.1;
+    let res = update_stats(store, make_session(&words, &info, &summary));
+    if let Err(err) = res {
+        println!("Failed to save the result: {}", err)
+    }
 
-        content[&letter]["attempts"].push(object! {
-            acc: value["acc"].clone(),
-            wpm: value["wpm"].clone(),
-        }).expect("There is something wrong with the json.");
+    let res = update_ngram_stats(store, &words);
+    if let Err(err) = res {
+        println!("Failed to save the n-gram statistics: {}", err)
     }
-    write_json_to_file(content)?;
-    Ok(())
+    report
 }
 
 
-pub fn show_stats() -> Result<(), io::Error> {
+fn update_stats(store: &mut dyn StatsStore, session: JsonValue) -> Result<(), io::Error> {
+    store.add_session(&session)
+}
+
+
+pub fn show_stats(store: &mut dyn StatsStore) -> Result<(), io::Error> {
 
-    let content = get_json_from_file()?;
+    let mut entries: Vec<(String, JsonValue)> = vec![];
+    for key in store.tracked_keys()? {
+        let attempts = store.attempts(&key)?;
+        entries.push((key, attempts));
+    }
+    // All first, then letters before digits and symbols, with capitals next to their small letter.
+    entries.sort_by_key(|(key, _)| {
+        let is_letter = key.chars().all(char::is_alphabetic);
+        (key != "all", !is_letter, key.to_lowercase(), key.to_string())
+    });
+    for (index, row) in entries.chunks(STATS_COLUMNS).enumerate() {
+        if index > 0 {
+            println!();
+        }
+        print_stats_row(row);
+    }
+    Ok(())
+}
 
+fn print_stats_row(entries: &[(String, JsonValue)]) {
     let mut letter_line = String::from("           ");
     let mut first_line = String::from("accuracy %:");
     let mut second_line = String::from("wpm:       ");
 
-    for entry in content.entries() {
-        let letter = entry.0;
+    for entry in entries {
+        let letter = &entry.0;
         letter_line.push_str(format!("{letter:>7}").as_str());
 
-        let attempts = &entry.1["attempts"];
+        let attempts = &entry.1;
         let attempts_count = attempts.len();
 
         if attempts_count == 0 {
@@ -215,18 +637,61 @@ pub fn show_stats() -> Result<(), io::Error> {
     println!("{}", letter_line);
     println!("{}", first_line);
     println!("{}", second_line);
-    Ok(())
 }
 
+/// Formats seconds since the Unix epoch as a UTC date and time, like "2025-03-14 09:26".
+fn format_timestamp(seconds: u64) -> String {
+    // Days to a civil date, from Howard Hinnant's date algorithms.
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let time_of_day = seconds % 86_400;
+    format!("{year}-{month:02}-{day:02} {:02}:{:02}", time_of_day / 3_600, time_of_day % 3_600 / 60)
+}
 
-pub fn get_letter_data(letter: &String, is_wpm: bool) -> Vec<usize> {
-    let content = get_json_from_file();
-    if content.is_err() {
-        println!("Got this error while reading statistics from file: {:?}", content.as_ref().err())
+fn show_sessions(store: &mut dyn StatsStore) -> Result<(), io::Error> {
+    let sessions = store.latest_sessions(SESSION_ROWS)?;
+    if sessions.is_empty() {
+        println!("No sessions recorded yet.");
+        return Ok(());
     }
 
-    let content = content.unwrap();
-    let attempts = &content[letter]["attempts"];
+    println!("{:<17} {:<8} {:>7} {:>7} {:>6} {:>7}  source", "date (UTC)", "mode", "wpm", "acc %", "words", "time");
+    for session in &sessions {
+        let options: Vec<String> = session["options"].members().map(|option| format!(" --{}", option)).collect();
+        let duration = Duration::from_millis(session["duration_ms"].as_u64().unwrap_or(0));
+        println!(
+            "{:<17} {:<8} {:>7.1} {:>7.1} {:>6} {:>6}s  {}{}",
+            format_timestamp(session["timestamp"].as_u64().unwrap_or(0)),
+            session["mode"].as_str().unwrap_or("?"),
+            session["all"]["wpm"].as_f64().unwrap_or(0.0),
+            session["all"]["acc"].as_f64().unwrap_or(0.0),
+            session["word_count"].as_usize().unwrap_or(0),
+            duration.as_secs(),
+            session["source"].as_str().unwrap_or("-"),
+            options.concat(),
+        );
+    }
+    Ok(())
+}
+
+
+pub fn get_letter_data(store: &mut dyn StatsStore, letter: &str, is_wpm: bool) -> Vec<usize> {
+    let attempts = store.attempts(letter);
+    let attempts = match attempts {
+        Ok(attempts) => attempts,
+        Err(err) => {
+            println!("Got this error while reading statistics from file: {}", err);
+            return vec![];
+        }
+    };
     let wpm_or_acc = if is_wpm { "wpm" } else { "acc" };
 
     let nums_in_string: Vec<String> = attempts
@@ -240,4 +705,204 @@ pub fn get_letter_data(letter: &String, is_wpm: bool) -> Vec<usize> {
         .map(|ok_res| ok_res.unwrap() as usize)
         .collect();
     nums
-}
\ No newline at end of file
+}
+
+/// A letter scores its recent average wpm scaled by its average accuracy. Letters never typed
+/// are left out.
+pub fn get_weakest_letters(store: &mut dyn StatsStore, alphabet: &[char]) -> Result<Vec<char>, io::Error> {
+    let mut scores: Vec<(char, f64)> = vec![];
+    for &letter in alphabet {
+        let attempts = store.recent_attempts(&letter.to_string(), RECENT_ATTEMPTS)?;
+        if attempts.is_empty() {
+            continue;
+        }
+
+        let average = |key: &str| average_recent(&attempts, key).unwrap_or(0.0);
+        scores.push((letter, average("wpm") * average("acc") / 100f64));
+    }
+
+    scores.sort_by(|a, b| a.1.total_cmp(&b.1));
+    Ok(scores.iter().map(|score| score.0).collect())
+}
+
+#[derive(Default)]
+struct NgramResult {
+    count: u32,
+    errors: u32,
+    /// The first letters of a lesson have no transition time.
+    timed: u32,
+    time: Duration,
+}
+
+/// The time of an n-gram is the time spent on the transitions between its letters.
+fn collect_ngrams(words: &[Word], n: usize, results: &mut HashMap<String, NgramResult>) {
+    for word in words {
+        let letters: Vec<&Letter> = word.letters.iter().filter(|letter| !letter.letter.is_whitespace()).collect();
+
+        for window in letters.windows(n) {
+            let ngram: String = window.iter().map(|letter| letter.letter).collect();
+            let result = results.entry(ngram).or_default();
+
+            result.count += 1;
+            if window.iter().any(|letter| !letter.correct.unwrap_or(false)) {
+                result.errors += 1;
+            }
+
+            let time = window[window.len() - 1].latency(window[0]);
+            if let Some(time) = time {
+                result.timed += 1;
+                result.time += time;
+            }
+        }
+    }
+}
+
+fn update_ngram_stats(store: &mut dyn StatsStore, words: &[Word]) -> Result<(), io::Error> {
+    let mut results = HashMap::new();
+    collect_ngrams(words, 2, &mut results);
+    collect_ngrams(words, 3, &mut results);
+
+    let mut lesson = JsonValue::new_object();
+    for (ngram, result) in results {
+        lesson[ngram] = object! {
+            count: result.count,
+            errors: result.errors,
+            timed: result.timed,
+            time_ms: result.time.as_secs_f64() * 1000f64,
+        };
+    }
+    store.add_ngrams(&lesson)
+}
+
+struct NgramTotals {
+    ngram: String,
+    average_ms: f64,
+    accuracy: f64,
+}
+
+fn get_ngram_totals(store: &mut dyn StatsStore) -> Result<Vec<NgramTotals>, io::Error> {
+    let content = store.ngram_totals()?;
+
+    let mut totals = vec![];
+    for (ngram, value) in content.entries() {
+        let count = value["count"].as_f64().unwrap_or(0.0);
+        let timed = value["timed"].as_f64().unwrap_or(0.0);
+        if count < MIN_NGRAM_COUNT || timed < 1.0 {
+            continue;
+        }
+
+        totals.push(NgramTotals {
+            ngram: ngram.to_string(),
+            average_ms: value["time_ms"].as_f64().unwrap_or(0.0) / timed,
+            accuracy: (1f64 - value["errors"].as_f64().unwrap_or(0.0) / count) * 100f64,
+        });
+    }
+    Ok(totals)
+}
+
+fn print_ngram_table(title: &str, totals: &[NgramTotals]) {
+    println!("{title:<16}{:>8}{:>12}", "ms", "accuracy %");
+    for total in totals.iter().take(NGRAM_TABLE_ROWS) {
+        println!("{:<16}{:>8.0}{:>12.1}", total.ngram, total.average_ms, total.accuracy);
+    }
+}
+
+pub fn show_ngram_stats(store: &mut dyn StatsStore) -> Result<(), io::Error> {
+    let mut totals = get_ngram_totals(store)?;
+    if totals.is_empty() {
+        println!("Not enough n-gram statistics yet.");
+        return Ok(());
+    }
+
+    totals.sort_by(|a, b| b.average_ms.total_cmp(&a.average_ms));
+    print_ngram_table("slowest", &totals);
+    println!();
+
+    totals.sort_by(|a, b| a.accuracy.total_cmp(&b.accuracy));
+    print_ngram_table("least accurate", &totals);
+    Ok(())
+}
+
+/// The average transition time is divided by the accuracy, so an often mistyped n-gram counts
+/// as slower.
+pub fn get_weakest_ngrams(store: &mut dyn StatsStore) -> Result<Vec<String>, io::Error> {
+    let mut totals = get_ngram_totals(store)?;
+    let score = |total: &NgramTotals| total.average_ms / total.accuracy.max(1.0);
+    totals.sort_by(|a, b| score(b).total_cmp(&score(a)));
+    Ok(totals.into_iter().map(|total| total.ngram).collect())
+}
+
+pub fn show_stats_view(store: &mut dyn StatsStore, view: &StatsView) -> Result<(), io::Error> {
+    match view {
+        StatsView::Letters => show_stats(store),
+        StatsView::Ngrams => show_ngram_stats(store),
+        StatsView::Sessions => show_sessions(store),
+    }
+}
+
+/// 0 for a text not seen before.
+pub fn get_text_position(key: &str) -> Result<usize, io::Error> {
+    let content = get_json_from_file(&get_text_progress_path())?;
+    Ok(content[key]["position"].as_usize().unwrap_or(0))
+}
+
+pub fn save_text_position(key: &str, position: usize) -> Result<(), io::Error> {
+    let path = get_text_progress_path();
+    let _lock = lock_data(&path)?;
+    let mut content = get_json_from_file(&path)?;
+    content[key] = object! { position: position };
+    write_json_to_file(&path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(timestamp: u64, wpm: f64) -> JsonValue {
+        object! { timestamp: timestamp, mode: "words", all: { acc: 100.0, wpm: wpm }, letters: {} }
+    }
+
+    fn add_all(store: &mut dyn StatsStore, sessions: &[JsonValue]) {
+        for session in sessions {
+            store.add_session(session).unwrap();
+        }
+    }
+
+    #[test]
+    fn copies_only_the_sessions_the_other_store_lacks() {
+        let sessions = vec![session(1, 10.0), session(2, 20.0), session(2, 21.0), session(3, 30.0)];
+        let mut from = SqliteStore::in_memory();
+        let mut to = SqliteStore::in_memory();
+        add_all(&mut from, &sessions);
+        // The same second as a session that is already there is no reason to skip one.
+        add_all(&mut to, &sessions[..2]);
+
+        copy_new_sessions(&mut from, &mut to).unwrap();
+        assert_eq!(to.latest_sessions(10).unwrap(), sessions);
+
+        copy_new_sessions(&mut from, &mut to).unwrap();
+        assert_eq!(to.latest_sessions(10).unwrap(), sessions);
+    }
+
+    #[test]
+    fn copies_a_long_history_into_an_empty_store() {
+        let sessions: Vec<JsonValue> = (0..100).map(|timestamp| session(timestamp, 50.0)).collect();
+        let mut from = SqliteStore::in_memory();
+        let mut to = SqliteStore::in_memory();
+        add_all(&mut from, &sessions);
+
+        copy_new_sessions(&mut from, &mut to).unwrap();
+        assert_eq!(to.latest_sessions(1000).unwrap(), sessions);
+    }
+
+    #[test]
+    fn sessions_since_reads_back_far_enough() {
+        let sessions: Vec<JsonValue> = (0..100).map(|timestamp| session(timestamp / 2, 50.0)).collect();
+        let mut store = SqliteStore::in_memory();
+        add_all(&mut store, &sessions);
+
+        assert_eq!(sessions_since(&mut store, 10).unwrap(), sessions[20..]);
+        assert_eq!(sessions_since(&mut store, 0).unwrap(), sessions);
+        assert!(sessions_since(&mut store, 50).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,29 @@
+use std::io;
+
+use json::JsonValue;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub enum StoreKind {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+/// Attempts and n-gram totals are handed over as JSON in the same shape from either store.
+pub trait StatsStore {
+    fn add_session(&mut self, session: &JsonValue) -> Result<(), io::Error>;
+
+    /// Oldest first, like every list of sessions or attempts here.
+    fn latest_sessions(&mut self, count: usize) -> Result<Vec<JsonValue>, io::Error>;
+
+    /// Attempts saved before there was a session history come first.
+    fn attempts(&mut self, key: &str) -> Result<JsonValue, io::Error>;
+
+    fn recent_attempts(&mut self, key: &str, count: usize) -> Result<JsonValue, io::Error>;
+
+    fn tracked_keys(&mut self) -> Result<Vec<String>, io::Error>;
+
+    fn add_ngrams(&mut self, lesson: &JsonValue) -> Result<(), io::Error>;
+
+    fn ngram_totals(&mut self) -> Result<JsonValue, io::Error>;
+}
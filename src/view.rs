@@ -0,0 +1,133 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write, stdout},
+};
+
+use crossterm::{
+    cursor::{self, MoveTo},
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+
+use crate::word_tree::{Letter, Word};
+
+/// How many lines after the current one are shown.
+pub const UPCOMING_LINES: usize = 2;
+/// The status line, the current line and the upcoming lines.
+const VIEW_ROWS: u16 = 2 + UPCOMING_LINES as u16;
+/// How many columns a tab in code takes.
+const TAB_WIDTH: usize = 4;
+
+/// How a letter is drawn. Tabs and newlines from code are drawn as arrows, so it is clear they
+/// have to be typed.
+fn shown(letter: char) -> String {
+    match letter {
+        '\t' => format!("{:<TAB_WIDTH$}", '\u{2192}'),
+        '\n' => String::from('\u{21B5}'),
+        _ => String::from(letter),
+    }
+}
+
+/// How many columns a letter takes when drawn.
+pub fn letter_width(letter: char) -> usize {
+    match letter {
+        '\t' => TAB_WIDTH,
+        _ => 1,
+    }
+}
+
+/// The part of the terminal used while typing. The top row is a status line, below it is the line
+/// being typed and below that the lines coming up next. Everything is drawn at fixed rows, so the
+/// text scrolls up a row each time a line is done.
+pub struct TextView {
+    top: u16,
+    /// Keep letters that were mistyped red after they are typed right, like the normal mode does.
+    mark_corrected: bool,
+    /// The last status drawn, so it can be drawn again after the screen is cleared.
+    status: String,
+}
+
+impl TextView {
+    /// Makes room for the view below the cursor, scrolling the terminal if it is at the bottom.
+    pub fn new(mark_corrected: bool) -> Result<TextView, io::Error> {
+        let mut stdout = stdout();
+        for _ in 1..VIEW_ROWS {
+            write!(stdout, "\r\n")?;
+        }
+        stdout.flush()?;
+
+        let (_, bottom) = cursor::position()?;
+        Ok(TextView { top: bottom.saturating_sub(VIEW_ROWS - 1), mark_corrected, status: String::new() })
+    }
+
+    pub fn draw_status(&mut self, status: &str) -> Result<(), io::Error> {
+        self.status = String::from(status);
+        let mut stdout = stdout();
+        queue!(
+            stdout,
+            cursor::SavePosition,
+            MoveTo(0, self.top),
+            Clear(ClearType::CurrentLine),
+            Print(status),
+            cursor::RestorePosition,
+        )?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Draws the current line with what has been typed so far, then the upcoming lines greyed out,
+    /// and puts the cursor `cursor` letters into the current line.
+    pub fn draw(&self, current: &[Word], upcoming: &VecDeque<Vec<Word>>, cursor: usize) -> Result<(), io::Error> {
+        let mut stdout = stdout();
+
+        queue!(stdout, MoveTo(0, self.top + 1), Clear(ClearType::CurrentLine))?;
+        for letter in current.iter().flat_map(|word| word.letters.iter()) {
+            if self.is_wrong(letter) {
+                queue!(stdout, SetForegroundColor(Color::Red), Print(shown(letter.letter)), ResetColor)?;
+            } else {
+                queue!(stdout, Print(shown(letter.letter)))?;
+            }
+        }
+
+        for row in 0..UPCOMING_LINES {
+            queue!(stdout, MoveTo(0, self.top + 2 + row as u16), Clear(ClearType::CurrentLine))?;
+            if let Some(line) = upcoming.get(row) {
+                let text: String = line
+                    .iter()
+                    .flat_map(|word| word.letters.iter())
+                    .map(|letter| shown(letter.letter))
+                    .collect();
+                queue!(stdout, SetForegroundColor(Color::DarkGrey), Print(text), ResetColor)?;
+            }
+        }
+
+        queue!(stdout, MoveTo(cursor as u16, self.top + 1))?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn is_wrong(&self, letter: &Letter) -> bool {
+        let wrong_input = letter.input.is_some_and(|input| input != letter.letter);
+        let corrected = self.mark_corrected && letter.correct == Some(false);
+        wrong_input || corrected
+    }
+
+    /// Clears the whole terminal and moves the view to the top. Used when the terminal is resized,
+    /// since the old rows can no longer be trusted after the terminal has rewrapped them.
+    pub fn clear(&mut self) -> Result<(), io::Error> {
+        let mut stdout = stdout();
+        queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+        self.top = 0;
+        let status = self.status.clone();
+        self.draw_status(&status)
+    }
+
+    /// Moves the cursor below the view, so anything printed after typing does not cover the text.
+    pub fn leave(&self) -> Result<(), io::Error> {
+        let mut stdout = stdout();
+        queue!(stdout, MoveTo(0, self.top + VIEW_ROWS - 1))?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
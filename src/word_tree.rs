@@ -1,26 +1,82 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::fs;
 use std::rc::Rc;
-use std::rc::Weak;
-use std::str::Chars;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::vec;
 
+use rand::Rng;
 use rand::seq::IndexedRandom;
 
-#[allow(dead_code)]
+/// A node in the DAWG. Nodes are shared between every word with the same ending, so a node has
+/// no single parent.
 #[derive(Debug)]
 pub struct Node {
-    pub parent: Option<Weak<RefCell<Node>>>,
     pub children: Vec<Rc<RefCell<Node>>>,
     pub letter: char,
+    /// True when a word from the word list ends at this node, even if longer words continue past it.
+    pub end_of_word: bool,
+    /// How many words end at this node or below it. With the words numbered in sorted order, the
+    /// words through a node have the next `count` numbers, which is how a word is found by number.
+    pub count: u64,
+    /// The letters of the nodes below this one, so a walk can skip subtrees without a letter.
+    reachable: LetterSet,
+}
+
+/// A set of letters from the alphabet of a word list, one bit for each letter by its position
+/// in the sorted alphabet.
+#[derive(Debug, Clone, Default)]
+struct LetterSet(Vec<u64>);
+
+impl LetterSet {
+    fn insert(&mut self, position: usize) {
+        let block = position / 64;
+        if self.0.len() <= block {
+            self.0.resize(block + 1, 0);
+        }
+        self.0[block] |= 1 << (position % 64);
+    }
+
+    fn contains(&self, position: usize) -> bool {
+        self.0.get(position / 64).is_some_and(|block| block & (1 << (position % 64)) != 0)
+    }
+
+    fn extend(&mut self, other: &LetterSet) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (block, other_block) in self.0.iter_mut().zip(&other.0) {
+            *block |= other_block;
+        }
+    }
+}
+
+/// How `Node` turns paths through the tree into words.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum GenMode {
+    /// Only words that are in the word list.
+    #[default]
+    Dictionary,
+    /// Pseudo-words made by splicing two words together, for letter-pattern drills.
+    Nonsense,
 }
 
 #[derive(Debug)]
 pub struct Letter {
     pub letter: char,
     pub correct: Option<bool>,
+    /// When a key was first pressed while this letter was the one to type.
+    pub first_attempt: Option<Instant>,
+    /// When this letter was finally typed correctly.
+    pub typed: Option<Instant>,
+    /// How many wrong keys were pressed before the right one.
+    pub wrong_attempts: u32,
+    /// How many keys were typed at this letter, right and wrong.
+    pub keystrokes: u32,
+    /// What is typed at this letter right now. Only differs from `letter` in real typing mode,
+    /// where wrong keys stay until they are deleted.
+    pub input: Option<char>,
 }
 
 #[derive(Debug)]
@@ -29,106 +85,429 @@ pub struct Word {
     pub output: String,
     #[allow(unused)]
     pub time: Option<Duration>,
+    /// The next word starts a new line, after a paragraph in a `--text` passage or a line of code.
+    pub ends_paragraph: bool,
 }
 
-impl Node {
-    pub fn new(file_name: String) -> Result<Rc<RefCell<Node>>, io::Error> {
+impl Letter {
+    pub fn new(letter: char) -> Letter {
+        Letter { letter, correct: None, first_attempt: None, typed: None, wrong_attempts: 0, keystrokes: 0, input: None }
+    }
 
-        let root = Rc::new(RefCell::new(Node { children: Vec::new(), parent: None, letter: '\0' }));
-        Self::file_handle(root.clone(), file_name)?;
+    /// Time from `previous` being typed until this letter was typed. None for the first letter
+    /// of a lesson or a letter that was never typed.
+    pub fn latency(&self, previous: &Letter) -> Option<Duration> {
+        let typed = self.typed?;
+        let previous_typed = previous.typed?;
+        Some(typed.saturating_duration_since(previous_typed))
+    }
 
-        return Ok(root);
+    /// Time from the first key pressed at this letter until the right one, for a letter that was
+    /// missed at first. None when the first key was right or the letter was never typed.
+    pub fn correction_time(&self) -> Option<Duration> {
+        if self.wrong_attempts == 0 {
+            return None;
+        }
+        Some(self.typed?.saturating_duration_since(self.first_attempt?))
     }
+}
 
-    fn file_handle(root: Rc<RefCell<Node>>, file_name: String) -> Result<(), io::Error> {
+impl Word {
+    pub fn new(text: &str) -> Word {
+        Word {
+            letters: text.chars().map(Letter::new).collect(),
+            output: String::from(text),
+            time: None,
+            ends_paragraph: false,
+        }
+    }
+}
 
-        let file = fs::read_to_string(file_name)?;
-        let split = file.split("\n");
+/// Takes the space or newline off the end of the last word of a lesson. The lesson ends on its
+/// last letter, there is nothing to type after it.
+pub fn trim_last_letter(words: &mut VecDeque<Word>) {
+    if let Some(last_word) = words.back_mut() {
+        last_word.letters.pop();
+        last_word.output.pop();
+    }
+}
+
+/// Highest weight given to a word when weights come from its rank in the file.
+const ZIPF_SCALE: u64 = 1 << 16;
+
+/// Identifies a node by everything that makes it interchangeable with another one: its letter,
+/// whether a word ends at it and which (already minimized) children it points to.
+#[derive(PartialEq, Eq, Hash)]
+struct NodeKey {
+    letter: char,
+    end_of_word: bool,
+    children: Vec<*const RefCell<Node>>,
+}
+
+impl NodeKey {
+    fn new(node: &Node) -> NodeKey {
+        NodeKey {
+            letter: node.letter,
+            end_of_word: node.end_of_word,
+            children: node.children.iter().map(Rc::as_ptr).collect(),
+        }
+    }
+}
 
-        for word in split {
-            let chars = word.chars();
-            Self::append_chars(root.clone(), chars)?;
+/// Builds a minimized DAWG incrementally from sorted words (Daciuk et al.). Only the nodes on
+/// the path of the last inserted word are unchecked, everything before that is already merged
+/// with any equal node in the register, so identical suffixes end up sharing nodes.
+struct DawgBuilder {
+    root: Rc<RefCell<Node>>,
+    alphabet: Vec<char>,
+    previous_word: Vec<char>,
+    unchecked: Vec<Rc<RefCell<Node>>>,
+    register: HashMap<NodeKey, Rc<RefCell<Node>>>,
+}
+
+impl DawgBuilder {
+    fn new(alphabet: Vec<char>) -> DawgBuilder {
+        DawgBuilder {
+            root: Rc::new(RefCell::new(Node::empty('\0'))),
+            alphabet,
+            previous_word: vec![],
+            unchecked: vec![],
+            register: HashMap::new(),
         }
-        
-        Ok(())
     }
 
-    fn append_chars(parent: Rc<RefCell<Node>>, mut chars: Chars<'_>) -> Result<(), io::Error> {
-        let letter_opt = chars.next();
-        if letter_opt.is_none() {
-            return Ok(())
+    fn insert(&mut self, word: &str) {
+        let chars: Vec<char> = word.chars().collect();
+        let common_prefix = chars
+            .iter()
+            .zip(self.previous_word.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        self.minimize(common_prefix);
+
+        for letter in &chars[common_prefix..] {
+            let node = Rc::new(RefCell::new(Node::empty(*letter)));
+            self.last_unchecked().borrow_mut().children.push(node.clone());
+            self.unchecked.push(node);
         }
+        self.last_unchecked().borrow_mut().end_of_word = true;
+        self.previous_word = chars;
+    }
 
-        let letter = letter_opt.unwrap();
+    fn last_unchecked(&self) -> Rc<RefCell<Node>> {
+        self.unchecked.last().unwrap_or(&self.root).clone()
+    }
 
-        let mut parent_borrow = parent.borrow_mut();
-        let children = &parent_borrow.children;
-        let child= children.iter().find(|child| child.borrow().letter == letter);
+    /// Merges the unchecked nodes deeper than `down_to` with equal nodes from the register.
+    fn minimize(&mut self, down_to: usize) {
+        while self.unchecked.len() > down_to {
+            let node = self.unchecked.pop().unwrap();
+            node.borrow_mut().update_summary(&self.alphabet);
+            let parent = self.last_unchecked();
+            let key = NodeKey::new(&node.borrow());
 
-        if child.is_some() {
-            Self::append_chars(child.unwrap().clone(), chars)?;
-            return Ok(());
-        };
+            match self.register.get(&key) {
+                Some(existing) => {
+                    let mut parent_borrow = parent.borrow_mut();
+                    let last_child = parent_borrow.children.len() - 1;
+                    parent_borrow.children[last_child] = existing.clone();
+                },
+                None => {
+                    self.register.insert(key, node);
+                },
+            }
+        }
+    }
 
-        let this_node = Rc::new(RefCell::new(Node {parent: Some(Rc::downgrade(&parent)), children: vec!(), letter: letter}));
-        parent_borrow.children.push(this_node.clone());
+    fn finish(mut self) -> Rc<RefCell<Node>> {
+        self.minimize(0);
+        self.root.borrow_mut().update_summary(&self.alphabet);
+        self.root
+    }
+}
+
+/// Weight for the word at `rank` in a file sorted by frequency, following Zipf's law.
+fn rank_weight(rank: usize) -> u64 {
+    (ZIPF_SCALE / (rank as u64 + 1)).max(1)
+}
+
+/// Reads a line as either `word` or `word<TAB>count`. Words without a count are weighted by rank.
+fn parse_weighted_line(line: &str, rank: usize) -> (&str, u64) {
+    let mut columns = line.split('\t');
+    let word = columns.next().unwrap_or("").trim();
 
-        Self::append_chars(this_node, chars)?;
-        return Ok(());
+    let count = columns.next().and_then(|count| count.trim().parse::<u64>().ok());
+    let weight = match count {
+        Some(count) => count.max(1),
+        None => rank_weight(rank),
+    };
+    (word, weight)
+}
+
+/// The word list as a minimized DAWG, with the weights of the words kept beside it. The words are
+/// numbered in sorted order and their weights looked up by number, so the nodes do not depend on
+/// the weights and every shared suffix is merged.
+pub struct WordTree {
+    root: Rc<RefCell<Node>>,
+    /// The summed weight of every word up to and including the word with that number, for picking
+    /// a number in proportion to the weights.
+    cumulative_weights: Vec<u64>,
+    /// Every letter in the word list, sorted.
+    alphabet: Vec<char>,
+}
+
+impl WordTree {
+    pub fn new(file_name: String) -> Result<WordTree, io::Error> {
+        let file = fs::read_to_string(file_name)?;
+        let tree = WordTree::from_text(&file);
+        if tree.cumulative_weights.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "The word list has no words in it."));
+        }
+        Ok(tree)
     }
 
-    pub fn gen_word(&self) -> Word {
-        let child = self.children.choose(&mut rand::rng());
-        if child.is_none() {
-            return Word {
-                letters: vec![Letter {
-                    letter: self.letter,
-                    correct: None,
-                }],
-                output: String::from(self.letter),
-                time: None,
+    /// Builds the tree from the contents of a word list, one word per line.
+    fn from_text(file: &str) -> WordTree {
+        let mut words: Vec<(&str, u64)> = file
+            .split("\n")
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(rank, line)| parse_weighted_line(line, rank))
+            .filter(|(word, _)| !word.is_empty())
+            .collect();
+        words.sort();
+
+        let mut alphabet: Vec<char> = words.iter().flat_map(|(word, _)| word.chars()).collect();
+        alphabet.sort();
+        alphabet.dedup();
+
+        let mut builder = DawgBuilder::new(alphabet.clone());
+        let mut cumulative_weights = vec![];
+        let mut total = 0;
+        let mut index = 0;
+        while index < words.len() {
+            let word = words[index].0;
+            while index < words.len() && words[index].0 == word {
+                total += words[index].1;
+                index += 1;
             }
+            builder.insert(word);
+            cumulative_weights.push(total);
         }
-        let mut word = child.unwrap().borrow().gen_word();
-        if self.letter != '\0' {
-            word.output.insert(0, self.letter);
-            word.letters.insert(0, Letter { letter: self.letter, correct: None });
+
+        WordTree { root: builder.finish(), cumulative_weights, alphabet }
+    }
+
+    pub fn alphabet(&self) -> &[char] {
+        &self.alphabet
+    }
+
+    /// The summed weight of the words numbered below `number`.
+    fn weight_before(&self, number: u64) -> u64 {
+        match number {
+            0 => 0,
+            _ => self.cumulative_weights[number as usize - 1],
         }
+    }
 
-        word
+    fn word_weight(&self, number: u64) -> u64 {
+        self.weight_before(number + 1) - self.weight_before(number)
     }
 
+    /// Picks a word number from `first` up to `end` in proportion to the weights of the words.
+    fn pick_number(&self, rng: &mut impl Rng, first: u64, end: u64) -> u64 {
+        let before = self.weight_before(first);
+        let total = self.weight_before(end) - before;
+        let pick = before + rng.random_range(0..total.max(1));
+        self.number_at_weight(pick).clamp(first, end.max(first + 1) - 1)
+    }
 
-    #[allow(dead_code)]
-    pub fn gen_word_with_space(&self) -> Word {
-        let mut word = self.gen_word();
-        word.letters.push(Letter { letter: ' ', correct: None });
-        word.output = format!("{} ", word.output);
-        word
+    /// The number of the word whose share of the summed weights holds `weight`.
+    fn number_at_weight(&self, weight: u64) -> u64 {
+        self.cumulative_weights.partition_point(|summed| *summed <= weight) as u64
     }
 
-    #[allow(dead_code)]
-    pub fn gen_word_with(&self, include: char) -> Word {
+    /// Walks down to the word with `number`. At each node the word ending there comes first,
+    /// then the words through each child in order.
+    fn word_at(&self, mut number: u64) -> Word {
+        let mut text = String::new();
+        let mut node = self.root.clone();
         loop {
-            let word = self.gen_word();
-            if word.letters
-                .iter()
-                .map(|word| word.letter)
-                .any(|letter| letter == include) 
-            {
-                return word;
+            let next = {
+                let node_borrow = node.borrow();
+                if node_borrow.end_of_word {
+                    if number == 0 {
+                        break;
+                    }
+                    number -= 1;
+                }
+
+                let mut next = None;
+                for child in &node_borrow.children {
+                    let count = child.borrow().count;
+                    if number < count {
+                        next = Some(child.clone());
+                        break;
+                    }
+                    number -= count;
+                }
+                next
+            };
+
+            let Some(next) = next else {
+                break;
+            };
+            text.push(next.borrow().letter);
+            node = next;
+        }
+        Word::new(&text)
+    }
+
+    /// Picks a word in proportion to its weight and finds it in the tree. Prefix words like "the"
+    /// have their own number, so they come up as often as any other word with their weight.
+    /// All the randomness comes from `rng`, so a seeded rng always gives the same words.
+    pub fn gen_word(&self, rng: &mut impl Rng) -> Word {
+        let count = self.root.borrow().count;
+        self.word_at(self.pick_number(rng, 0, count))
+    }
+
+    /// Splices the start of one word onto the end of another at a letter they share, so
+    /// "water" and "thing" can give "wathing". Used for letter-pattern drills.
+    pub fn gen_nonsense_word(&self, rng: &mut impl Rng) -> Word {
+        let first = self.gen_word(rng).output;
+        let second = self.gen_word(rng).output;
+
+        let mut splice_points = vec![];
+        for (first_index, first_letter) in first.char_indices() {
+            for (second_index, second_letter) in second.char_indices() {
+                if first_letter == second_letter {
+                    splice_points.push((first_index + first_letter.len_utf8(), second_index + second_letter.len_utf8()));
+                }
             }
         }
+
+        let splice_point = splice_points.choose(rng);
+        match splice_point {
+            Some((first_end, second_start)) => Word::new(&format!("{}{}", &first[..*first_end], &second[*second_start..])),
+            None => Word::new(&first),
+        }
     }
 
-    #[allow(dead_code)]
-    pub fn gen_word_with_space_includes(&self, include: char) -> Word {
-        let mut word = self.gen_word_with(include);
-        word.letters.push(Letter { letter: ' ', correct: None });
+    pub fn gen_word_in(&self, rng: &mut impl Rng, mode: GenMode) -> Word {
+        match mode {
+            GenMode::Dictionary => self.gen_word(rng),
+            GenMode::Nonsense => self.gen_nonsense_word(rng),
+        }
+    }
+
+    pub fn gen_word_with_space(&self, rng: &mut impl Rng, mode: GenMode) -> Word {
+        let mut word = self.gen_word_in(rng, mode);
+        word.letters.push(Letter::new(' '));
         word.output = format!("{} ", word.output);
         word
     }
 
+    /// Generates a word containing `include`, picked among those words only in proportion to
+    /// their weights. The walk for them skips every subtree that cannot reach the letter, and
+    /// fails straight away if no word has it.
+    pub fn gen_word_with(&self, rng: &mut impl Rng, include: char) -> Result<Word, io::Error> {
+        let Ok(position) = self.alphabet.binary_search(&include) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No word in the word list contains the letter '{include}'."),
+            ));
+        };
+
+        let mut ranges = vec![];
+        self.root.borrow().ranges_containing(include, position, 0, &mut ranges);
+
+        let weight = |(first, end): (u64, u64)| self.weight_before(end) - self.weight_before(first);
+        let total: u64 = ranges.iter().copied().map(weight).sum();
+        let mut pick = rng.random_range(0..total.max(1));
+        for range in ranges {
+            if pick < weight(range) {
+                return Ok(self.word_at(self.number_at_weight(self.weight_before(range.0) + pick)));
+            }
+            pick -= weight(range);
+        }
+        unreachable!("the alphabet only has letters from the word list")
+    }
+
+    pub fn gen_word_with_space_includes(&self, rng: &mut impl Rng, include: char) -> Result<Word, io::Error> {
+        let mut word = self.gen_word_with(rng, include)?;
+        word.letters.push(Letter::new(' '));
+        word.output = format!("{} ", word.output);
+        Ok(word)
+    }
+
+    /// Every word in the tree that contains `pattern`, with its weight.
+    pub fn words_containing(&self, pattern: &str) -> Vec<(String, u64)> {
+        let mut words = vec![];
+        self.root.borrow().collect_words(&mut String::new(), &mut 0, &mut |word, number| {
+            if word.contains(pattern) {
+                words.push((String::from(word), self.word_weight(number)));
+            }
+        });
+        words
+    }
+}
+
+impl Node {
+    fn empty(letter: char) -> Node {
+        Node { children: vec![], letter, end_of_word: false, count: 0, reachable: LetterSet::default() }
+    }
+
+    /// Counts the words through this node and the letters below it. Called once the children
+    /// are final.
+    fn update_summary(&mut self, alphabet: &[char]) {
+        self.count = self.end_of_word as u64;
+        self.reachable = LetterSet::default();
+        for child in &self.children {
+            let child = child.borrow();
+            self.count += child.count;
+            self.reachable.extend(&child.reachable);
+            if let Ok(position) = alphabet.binary_search(&child.letter) {
+                self.reachable.insert(position);
+            }
+        }
+    }
+
+    /// Adds the number ranges of the words below this node that contain `include`, at `position`
+    /// in the alphabet. `first` is the number of the first word through this node, and no letter
+    /// on the way here is `include`. Below a node with the letter every word has it, so those
+    /// are taken as a whole range.
+    fn ranges_containing(&self, include: char, position: usize, first: u64, ranges: &mut Vec<(u64, u64)>) {
+        let mut number = first + self.end_of_word as u64;
+        for child in &self.children {
+            let child = child.borrow();
+            if child.letter == include {
+                ranges.push((number, number + child.count));
+            } else if child.reachable.contains(position) {
+                child.ranges_containing(include, position, number, ranges);
+            }
+            number += child.count;
+        }
+    }
+
+    /// Calls `found` with every word through this node and its number, in number order.
+    fn collect_words(&self, prefix: &mut String, number: &mut u64, found: &mut dyn FnMut(&str, u64)) {
+        if self.letter != '\0' {
+            prefix.push(self.letter);
+        }
+        if self.end_of_word {
+            found(prefix, *number);
+            *number += 1;
+        }
+        for child in &self.children {
+            child.borrow().collect_words(prefix, number, found);
+        }
+        if self.letter != '\0' {
+            prefix.pop();
+        }
+    }
+
     #[allow(dead_code)]
     pub fn walk(&self, f: &dyn Fn(&Node), bubble: bool) {
         if bubble {
@@ -140,4 +519,139 @@ impl Node {
             self.children.iter().for_each(|child| child.borrow().walk(f, bubble));
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    const WORDS: &str = "the\nthere\nthem\nthese\nother\nwater\nwalking\ntalking\n";
+
+    fn generate(tree: &WordTree, seed: u64, count: usize) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count).map(|_| tree.gen_word(&mut rng).output).collect()
+    }
+
+    fn count_nodes(node: &Rc<RefCell<Node>>, seen: &mut HashSet<*const RefCell<Node>>) {
+        if seen.insert(Rc::as_ptr(node)) {
+            for child in &node.borrow().children {
+                count_nodes(child, seen);
+            }
+        }
+    }
+
+    fn child(node: &Rc<RefCell<Node>>, letter: char) -> Rc<RefCell<Node>> {
+        node.borrow().children.iter().find(|child| child.borrow().letter == letter).unwrap().clone()
+    }
+
+    #[test]
+    fn prefix_words_come_out() {
+        let tree = WordTree::from_text(WORDS);
+
+        assert!(generate(&tree, 2, 1000).iter().any(|word| word == "the"));
+    }
+
+    #[test]
+    fn suffixes_are_shared() {
+        // Different counts must not keep the endings apart.
+        let tree = WordTree::from_text("balking\t3\ntalking\t70\nwalking\t900\n");
+
+        let mut seen = HashSet::new();
+        count_nodes(&tree.root, &mut seen);
+        // The root, the three first letters and one shared "alking".
+        assert_eq!(seen.len(), 1 + 3 + 6);
+        assert!(Rc::ptr_eq(&child(&child(&tree.root, 'b'), 'a'), &child(&child(&tree.root, 'w'), 'a')));
+    }
+
+    #[test]
+    fn empty_word_lists_are_rejected() {
+        let dir = ScratchDir::new("words-empty");
+        let path = dir.path().join("words");
+        fs::write(&path, "\n  \n\n").unwrap();
+
+        let err = WordTree::new(path.to_string_lossy().to_string()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn counts_in_the_list_are_followed() {
+        let tree = WordTree::from_text("common\t1000\nrare\t1\n");
+
+        let common = generate(&tree, 4, 1000).iter().filter(|word| *word == "common").count();
+        assert!(common > 950, "common came up {common} times out of 1000");
+    }
+
+    #[test]
+    fn generated_words_are_in_the_list() {
+        let tree = WordTree::from_text(WORDS);
+        let list: HashSet<&str> = WORDS.lines().collect();
+
+        for word in generate(&tree, 1, 1000) {
+            assert!(list.contains(word.as_str()), "{word} is not in the list");
+        }
+    }
+
+    #[test]
+    fn words_with_a_letter_contain_it() {
+        let tree = WordTree::from_text(WORDS);
+        let mut rng = StdRng::seed_from_u64(5);
+
+        for _ in 0..200 {
+            assert!(tree.gen_word_with(&mut rng, 'k').unwrap().output.contains('k'));
+        }
+    }
+
+    #[test]
+    fn words_with_a_letter_follow_their_counts() {
+        // "rake" is under the a subtree with "rain" and "rat", "rock" alone under o.
+        let tree = WordTree::from_text("rain\t500\nrat\t500\nrake\t10\nrock\t990\n");
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let rock = (0..1000).filter(|_| tree.gen_word_with(&mut rng, 'k').unwrap().output == "rock").count();
+        assert!(rock > 950, "rock came up {rock} times out of 1000");
+    }
+
+    #[test]
+    fn reachable_letters_are_the_letters_below() {
+        let tree = WordTree::from_text(WORDS);
+        let position = |letter: char| tree.alphabet.binary_search(&letter).unwrap();
+
+        let walking = child(&tree.root, 'w');
+        assert!(walking.borrow().reachable.contains(position('k')));
+        assert!(!walking.borrow().reachable.contains(position('w')));
+        assert!(!child(&tree.root, 'o').borrow().reachable.contains(position('k')));
+    }
+
+    #[test]
+    fn words_with_a_missing_letter_fail() {
+        let tree = WordTree::from_text(WORDS);
+        let mut rng = StdRng::seed_from_u64(6);
+
+        let err = tree.gen_word_with(&mut rng, 'z').unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn correction_time_is_from_the_first_key_to_the_right_one() {
+        let start = Instant::now();
+        let mut letter = Letter::new('a');
+        letter.first_attempt = Some(start);
+        letter.typed = Some(start + Duration::from_millis(400));
+        assert_eq!(letter.correction_time(), None);
+
+        letter.wrong_attempts = 2;
+        assert_eq!(letter.correction_time(), Some(Duration::from_millis(400)));
+    }
+
+    #[test]
+    fn same_seed_gives_same_words() {
+        let tree = WordTree::from_text(WORDS);
+
+        assert_eq!(generate(&tree, 3, 50), generate(&tree, 3, 50));
+    }
+}